@@ -0,0 +1,125 @@
+// Compares the flat, bit-packed wall storage of Grid with the nested Vec<Vec<Cell>>
+// of six bools per cell it replaced, which is reproduced here.
+//
+//   cargo bench --bench grid_storage [-- <width> <height>]
+extern crate maze;
+extern crate rand;
+
+use std::env;
+use std::mem;
+use std::time::{Duration, Instant};
+use rand::{Rng, SeedableRng, StdRng};
+use maze::{Direction, Grid};
+
+const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::South, Direction::East, Direction::West];
+
+#[derive(Clone)]
+struct NestedCell {
+    north: bool,
+    south: bool,
+    east: bool,
+    west: bool,
+    up: bool,
+    down: bool
+}
+
+impl NestedCell {
+    fn get(&self, dir: Direction) -> bool {
+        match dir {
+            Direction::North => self.north,
+            Direction::South => self.south,
+            Direction::East  => self.east,
+            Direction::West  => self.west,
+            Direction::Up    => self.up,
+            Direction::Down  => self.down
+        }
+    }
+}
+
+struct NestedGrid {
+    cells: Vec<Vec<NestedCell>>
+}
+
+impl NestedGrid {
+    fn new(width: usize, height: usize) -> NestedGrid {
+        let cell = NestedCell { north: true, south: true, east: true, west: true, up: true, down: true };
+        NestedGrid { cells: vec![vec![cell; height]; width] }
+    }
+
+    // Both sides of a wall are stored, and kept in sync
+    fn open(&mut self, x: usize, y: usize, dir: Direction) {
+        match dir {
+            Direction::East  => { self.cells[x][y].east = false; self.cells[x + 1][y].west = false; }
+            Direction::South => { self.cells[x][y].south = false; self.cells[x][y + 1].north = false; }
+            _                => unreachable!()
+        }
+    }
+}
+
+// The walls a binary tree maze opens, toward the south or the east of every cell
+fn binary_tree(width: usize, height: usize) -> Vec<(usize, usize, Direction)> {
+    let mut rng = StdRng::from_seed(&[42][..]);
+    let mut walls = Vec::with_capacity(width * height);
+    for x in 0 .. width {
+        for y in 0 .. height {
+            let dir = match (x + 1 < width, y + 1 < height) {
+                (true, true)   => if rng.gen() { Direction::East } else { Direction::South },
+                (true, false)  => Direction::East,
+                (false, true)  => Direction::South,
+                (false, false) => continue
+            };
+            walls.push((x, y, dir));
+        }
+    }
+    walls
+}
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1e3 + f64::from(duration.subsec_nanos()) / 1e6
+}
+
+fn report(name: &str, bytes: usize, build: Duration, carve: Duration, scan: Duration) {
+    println!("{:<8} {:>10.1} MiB {:>10.1} ms {:>10.1} ms {:>10.1} ms", name, bytes as f64 / (1 << 20) as f64,
+        millis(build), millis(carve), millis(scan));
+}
+
+fn main() {
+    let args: Vec<usize> = env::args().skip(1).filter_map(|arg| arg.parse().ok()).collect();
+    let (width, height) = match args[..] {
+        [width, height, ..] => (width, height),
+        _                   => (4000, 4000)
+    };
+    let walls = binary_tree(width, height);
+    println!("{} x {} cells, {} walls opened, every wall read once", width, height, walls.len());
+    println!("{:<8} {:>14} {:>13} {:>13} {:>13}", "storage", "memory", "allocate", "carve", "scan");
+
+    let (mut nested, build) = time(|| NestedGrid::new(width, height));
+    let ((), carve) = time(|| for &(x, y, dir) in &walls { nested.open(x, y, dir); });
+    let (closed, scan) = time(|| {
+        nested.cells.iter().flat_map(|column| column.iter())
+            .map(|cell| DIRECTIONS.iter().filter(|&&dir| cell.get(dir)).count())
+            .sum::<usize>()
+    });
+    let bytes = width * (mem::size_of::<Vec<NestedCell>>() + height * mem::size_of::<NestedCell>());
+    report("nested", bytes, build, carve, scan);
+    drop(nested);
+
+    let (mut grid, build) = time(|| Grid::new(width, height, true));
+    let ((), carve) = time(|| for &(x, y, dir) in &walls { grid.open(x, y, dir); });
+    let (flat_closed, scan) = time(|| {
+        (0 .. width).flat_map(|x| (0 .. height).map(move |y| (x, y)))
+            .map(|(x, y)| DIRECTIONS.iter().filter(|&&dir| grid.has_wall(x, y, dir)).count())
+            .sum::<usize>()
+    });
+    // Two bits per cell, and one per cell on the west and north borders
+    let bytes = (2 * width * height + width + height) / 8;
+    report("flat", bytes, build, carve, scan);
+
+    assert_eq!(closed, flat_closed);
+}
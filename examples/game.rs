@@ -0,0 +1,269 @@
+// Walk a maze in the terminal, from one entry point to the other.
+//
+//   cargo run --example game -- [algorithm [key=value ...]] [width] [height] [--fog] [--shift]
+//
+// The algorithm is written as in the text format, e.g. "binary-tree bias=sw". Move
+// with the arrow keys or WASD. 'f' toggles the fog of war, 'p' shows the solution from
+// where you stand and 'q' quits. With --shift the maze keeps changing around you, a
+// little after every move, as long as it's perfect. The terminal is switched to raw
+// mode with stty, so this runs on Unix-like systems.
+extern crate maze;
+extern crate rand;
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::process::{self, Command, Stdio};
+use maze::{Algorithm, Direction, Grid, Maze, MazeText, OriginShift};
+use maze::solver;
+use rand::ThreadRng;
+
+const PLAYER: &str = "\x1b[1;33m@@\x1b[0m";
+const GOAL: &str = "\x1b[1;32m[]\x1b[0m";
+const PATH: &str = "\x1b[36m::\x1b[0m";
+const HIDDEN: char = '\u{2591}';
+// How many times the origin of a shifting maze moves after every move
+const SHIFT_STEPS: usize = 20;
+const USAGE: &str = "Usage: game [algorithm [key=value ...]] [width] [height] [--fog] [--shift]";
+
+enum Key {
+    Move(Direction),
+    Fog,
+    Solution,
+    Quit,
+    Other
+}
+
+struct Game {
+    grid: Grid,
+    // The drawing of the maze, without the header of the text format
+    drawing: Vec<Vec<char>>,
+    player: (usize, usize),
+    goal: (usize, usize),
+    moves: usize,
+    seen: Vec<Vec<bool>>,
+    fog: bool,
+    solution: bool,
+    shift: Option<OriginShift<ThreadRng>>
+}
+
+impl Game {
+    fn new(grid: Grid, fog: bool, shift: bool) -> Game {
+        let drawing = drawing(&grid);
+        // A torus has no entry points, so it is crossed from corner to corner
+        let (player, goal) = match &solver::entry_points(&grid)[..] {
+            &[start, goal, ..] => (start, goal),
+            _                  => ((0, 0), (grid.width - 1, grid.height - 1))
+        };
+        let seen = vec![vec![false; grid.height]; grid.width];
+        let shift = if shift { Some(OriginShift::new(grid.clone(), rand::thread_rng())) } else { None };
+        let mut game = Game { grid, drawing, player, goal, moves: 0, seen, fog, solution: false, shift };
+        game.look_around();
+        game
+    }
+
+    fn step(&mut self, dir: Direction) {
+        let (x, y) = self.player;
+        if let Some(next) = self.grid.passage(x, y, dir) {
+            self.player = next;
+            self.moves += 1;
+            if let Some(ref mut shift) = self.shift {
+                shift.step(SHIFT_STEPS);
+                self.grid = shift.grid().clone();
+                self.drawing = drawing(&self.grid);
+            }
+            self.look_around();
+        }
+    }
+
+    // The player sees the cell they stand on and the cells right next to it
+    fn look_around(&mut self) {
+        let (x, y) = self.player;
+        self.seen[x][y] = true;
+        for &dir in &[Direction::North, Direction::South, Direction::East, Direction::West] {
+            if let Some((nx, ny)) = self.grid.passage(x, y, dir) {
+                self.seen[nx][ny] = true;
+            }
+        }
+    }
+
+    // Whether a character of the drawing touches a seen cell
+    fn visible(&self, row: usize, col: usize) -> bool {
+        if !self.fog {
+            return true;
+        }
+        let xs = if col.is_multiple_of(3) { [col / 3, (col / 3).wrapping_sub(1)] } else { [col / 3; 2] };
+        let ys = if row.is_multiple_of(2) { [row / 2, (row / 2).wrapping_sub(1)] } else { [row / 2; 2] };
+        xs.iter().any(|&x| ys.iter().any(|&y| {
+            x < self.grid.width && y < self.grid.height && self.seen[x][y]
+        }))
+    }
+
+    fn draw<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let path = if self.solution {
+            solver::shortest_path(&self.grid, self.player, self.goal).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        // Raw mode doesn't return the cursor to the start of a line
+        write!(out, "\x1b[H\x1b[2J")?;
+        for (row, line) in self.drawing.iter().enumerate() {
+            let mut col = 0;
+            while col < line.len() {
+                let cell = (col / 3, row / 2);
+                let inside = row % 2 == 1 && col % 3 == 1;
+                if inside && cell == self.player {
+                    write!(out, "{}", PLAYER)?;
+                } else if inside && cell == self.goal && self.visible(row, col) {
+                    write!(out, "{}", GOAL)?;
+                } else if inside && path.contains(&cell) {
+                    write!(out, "{}", PATH)?;
+                } else if inside {
+                    for c in &line[col .. col + 2] {
+                        write!(out, "{}", if self.visible(row, col) { *c } else { HIDDEN })?;
+                    }
+                } else {
+                    write!(out, "{}", if self.visible(row, col) { line[col] } else { HIDDEN })?;
+                    col += 1;
+                    continue;
+                }
+                col += 2;
+            }
+            write!(out, "\r\n")?;
+        }
+        write!(out, "\r\nMoves: {}   arrows/WASD: move  f: fog  p: solution  q: quit\r\n", self.moves)?;
+        if self.player == self.goal {
+            write!(out, "You made it out in {} moves!\r\n", self.moves)?;
+        }
+        out.flush()
+    }
+}
+
+// The drawing of the maze, without the header of the text format
+fn drawing(grid: &Grid) -> Vec<Vec<char>> {
+    MazeText::new(grid).to_string()
+        .lines()
+        .skip_while(|line| !line.starts_with('+'))
+        .map(|line| line.chars().collect())
+        .collect()
+}
+
+// Puts the terminal in raw mode, and back in its old mode when dropped
+struct RawMode {
+    saved: String
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?25l");
+        Ok(RawMode { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?25h");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_key<R: Read>(input: &mut R) -> io::Result<Option<Key>> {
+    let mut byte = [0u8];
+    if input.read(&mut byte)? == 0 {
+        return Ok(None);
+    }
+    let key = match byte[0] {
+        b'w' | b'W' => Key::Move(Direction::North),
+        b's' | b'S' => Key::Move(Direction::South),
+        b'd' | b'D' => Key::Move(Direction::East),
+        b'a' | b'A' => Key::Move(Direction::West),
+        b'f' | b'F' => Key::Fog,
+        b'p' | b'P' => Key::Solution,
+        b'q' | b'Q' | 3 => Key::Quit,
+        // The arrow keys are sent as "ESC [ A" to "ESC [ D"
+        27 => {
+            let mut sequence = [0u8; 2];
+            input.read_exact(&mut sequence)?;
+            match sequence {
+                [b'[', b'A'] => Key::Move(Direction::North),
+                [b'[', b'B'] => Key::Move(Direction::South),
+                [b'[', b'C'] => Key::Move(Direction::East),
+                [b'[', b'D'] => Key::Move(Direction::West),
+                _            => Key::Other
+            }
+        }
+        _ => Key::Other
+    };
+    Ok(Some(key))
+}
+
+// Origin shift only moves through a perfect maze without crossings
+fn shifts(algorithm: Algorithm) -> bool {
+    match algorithm {
+        Algorithm::WeaveKruskalsAlgorithm(_) | Algorithm::CellularAutomaton(_) => false,
+        Algorithm::RecursiveDivisionWith(division) => division.room_size == 1 && division.passages == 1,
+        Algorithm::BlobbyDivision(cells) => cells == 1,
+        _ => true
+    }
+}
+
+fn usage(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let fog = args.iter().any(|arg| arg == "--fog");
+    let shift = args.iter().any(|arg| arg == "--shift");
+    if let Some(flag) = args.iter().find(|arg| arg.starts_with("--") && *arg != "--fog" && *arg != "--shift") {
+        usage(&format!("Unknown option '{}'.", flag));
+    }
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).filter(|arg| !arg.starts_with("--")).collect();
+    // The settings follow the name of the algorithm
+    let end = args.iter().skip(1).take_while(|arg| arg.contains('=')).count() + 1;
+    let (algorithm, sizes) = match args.len() {
+        0 => (String::from("recursive-backtracking"), &args[..]),
+        _ => (args[.. end].join(" "), &args[end ..])
+    };
+    let algorithm: Algorithm = algorithm.parse().unwrap_or_else(|err: io::Error| usage(&err.to_string()));
+    if shift && !shifts(algorithm) {
+        eprintln!("'{}' makes crossings or loops, so the maze can't shift.", algorithm);
+        process::exit(1);
+    }
+    let size = |arg: Option<&&str>, default: usize| match arg.map(|size| size.parse()) {
+        None                       => default,
+        Some(Ok(size)) if size > 0 => size,
+        _                          => usage("The width and height must be numbers greater than 0.")
+    };
+    if sizes.len() > 2 {
+        usage(&format!("Unexpected argument '{}'.", sizes[2]));
+    }
+    let (width, height) = (size(sizes.first(), 20), size(sizes.get(1), 10));
+    let grid = Maze::new(width, height).algorithm(algorithm).generate();
+
+    let mut game = Game::new(grid, fog, shift);
+    let _raw_mode = RawMode::enable().expect("Can't switch the terminal to raw mode");
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    game.draw(&mut out).unwrap();
+    while game.player != game.goal {
+        match read_key(&mut input).unwrap() {
+            Some(Key::Move(dir)) => game.step(dir),
+            Some(Key::Fog)       => game.fog = !game.fog,
+            Some(Key::Solution)  => game.solution = !game.solution,
+            Some(Key::Quit) | None => break,
+            Some(Key::Other)     => continue
+        }
+        game.draw(&mut out).unwrap();
+    }
+}
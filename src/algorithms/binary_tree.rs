@@ -10,10 +10,9 @@ use super::Bias;
 // 3. So long as we keep to those two direction there is no danger of creating a loop
 //       or leaving part of the maze inaccessible.
 // Edge case: we have to be careful not to open a passage outside of the maze.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R, bias: Bias) -> Grid {
-    // Start with a raw maze
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, bias: Bias) {
     // Run over the maze, carving in a direction chosen by choose()
-    let mut grid = Grid::new(width, height, true);
+    let (width, height) = (grid.width, grid.height);
     for x in 0 .. width {
         for y in 0 .. height {
             let directions = collect_directions(bias, x, y, width, height);
@@ -22,7 +21,6 @@ pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R, bias: Bias) -> Grid
             }
         }
     }
-    grid
 }
 
 fn collect_directions(bias: Bias, x: usize, y: usize, width: usize, height: usize) -> Vec<Direction> {
@@ -0,0 +1,79 @@
+use rand::Rng;
+use rand::seq::sample_indices;
+use grid::Carve;
+
+// Based on http://weblog.jamisbuck.org/2015/1/15/better-recursive-division-algorithm
+// The algorithm:
+// 1. Start with all the cells of the maze in a single region.
+// 2. Pick two random cells of the region as the seeds of two blobs.
+// 3. Grow the blobs: take a random cell out of the blobs' frontier, and add its neighbors
+//      in the region to the same blob and to the frontier, until the frontier is empty.
+// 4. Build a wall between the two blobs, with a single passage through it.
+// 5. Repeat 2-4 on each blob, until a blob is no bigger than a room.
+// The grid starts closed and only the passages are opened, so it also carves regions
+// of any shape. The blobs reach every cell of a connected region. The pieces of a region
+// that isn't connected have no wall between them to open, so each is divided on its own
+// and the caller has to join them, the way regions::carve joins its parts. Rooms of
+// more than one cell add loops.
+pub fn carve<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, room_size: usize) {
+    // The region of every cell. Regions are only ever split.
+    let mut regions = vec![0; grid.size()];
+    let mut next_region = 1;
+    let mut stack = vec![(0 .. grid.size()).map(|i| grid.position(i)).collect::<Vec<_>>()];
+    while let Some(cells) = stack.pop() {
+        if cells.len() <= room_size.max(1) {
+            open_room(grid, &cells, &regions);
+            continue;
+        }
+        let region = regions[grid.index(cells[0])];
+        let (a, b) = (next_region, next_region + 1);
+        next_region += 2;
+        let mut frontier = Vec::new();
+        for (i, &blob) in sample_indices(rng, cells.len(), 2).iter().zip(&[a, b]) {
+            regions[grid.index(cells[*i])] = blob;
+            frontier.push(cells[*i]);
+        }
+        while !frontier.is_empty() {
+            let pos = frontier.swap_remove(rng.gen_range(0, frontier.len()));
+            let blob = regions[grid.index(pos)];
+            for &dir in grid.directions() {
+                if let Some(next) = grid.neighbor(pos, dir) {
+                    if regions[grid.index(next)] == region {
+                        regions[grid.index(next)] = blob;
+                        frontier.push(next);
+                    }
+                }
+            }
+        }
+        // The wall between the blobs, seen from the first one
+        let mut wall = Vec::new();
+        for &pos in cells.iter().filter(|&&pos| regions[grid.index(pos)] == a) {
+            for &dir in grid.directions() {
+                if grid.neighbor(pos, dir).is_some_and(|next| regions[grid.index(next)] == b) {
+                    wall.push((pos, dir));
+                }
+            }
+        }
+        if let Some(&(pos, dir)) = rng.choose(&wall) {
+            grid.open(pos, dir);
+        }
+        for &blob in &[a, b, region] {
+            let part: Vec<_> = cells.iter().cloned().filter(|&pos| regions[grid.index(pos)] == blob).collect();
+            if !part.is_empty() {
+                stack.push(part);
+            }
+        }
+    }
+}
+
+// Open all the walls between the cells of a room
+fn open_room<G: Carve>(grid: &mut G, cells: &[G::Pos], regions: &[usize]) {
+    for &pos in cells {
+        let region = regions[grid.index(pos)];
+        for &dir in grid.directions() {
+            if grid.neighbor(pos, dir).is_some_and(|next| regions[grid.index(next)] == region) {
+                grid.open(pos, dir);
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use rand::Rng;
+use grid::{Direction, Grid};
+use super::{Automaton, Rule};
+use super::kruskals_algorithm::Sets;
+
+// Based on https://conwaylife.com/wiki/OCA:Maze
+// The algorithm:
+// 1. Lay the cells, the walls between them and the posts at their corners out as the
+//      pixels of a lattice, 2 * width + 1 by 2 * height + 1. Bring density percent of
+//      the pixels to life at random.
+// 2. Run the rule on the lattice: a dead pixel comes to life with exactly 3 live
+//      neighbors, and a live one survives with 1 to 5 (Maze) or 1 to 4 (Mazectric).
+// 3. Stop after the given number of steps, or once the lattice doesn't change.
+// 4. Open the walls whose pixel is dead.
+// 5. Join the open regions the way Kruskal's algorithm joins cells, so every cell can be
+//      reached. The maze has loops.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, automaton: Automaton) {
+    let (width, height) = (2 * grid.width + 1, 2 * grid.height + 1);
+    let density = automaton.density.min(100);
+    let mut alive: Vec<bool> = (0 .. width * height).map(|_| rng.gen_range(0, 100) < density).collect();
+    for _ in 0 .. automaton.steps {
+        let next = step(&alive, width, height, automaton.rule);
+        if next == alive {
+            break;
+        }
+        alive = next;
+    }
+    for x in 0 .. grid.width {
+        for y in 0 .. grid.height {
+            if x + 1 < grid.width && !alive[(2 * x + 2) * height + 2 * y + 1] {
+                grid.open(x, y, Direction::East);
+            }
+            if y + 1 < grid.height && !alive[(2 * x + 1) * height + 2 * y + 2] {
+                grid.open(x, y, Direction::South);
+            }
+        }
+    }
+    join_regions(grid, rng);
+}
+
+// The next generation of the lattice. Pixels outside of it are dead.
+fn step(alive: &[bool], width: usize, height: usize, rule: Rule) -> Vec<bool> {
+    let mut next = vec![false; alive.len()];
+    for x in 0 .. width {
+        for y in 0 .. height {
+            let mut neighbors = 0;
+            for nx in x.saturating_sub(1) ..= (x + 1).min(width - 1) {
+                for ny in y.saturating_sub(1) ..= (y + 1).min(height - 1) {
+                    neighbors += ((nx, ny) != (x, y) && alive[nx * height + ny]) as usize;
+                }
+            }
+            next[x * height + y] = if alive[x * height + y] {
+                (1 ..= survival(rule)).contains(&neighbors)
+            } else {
+                neighbors == 3
+            };
+        }
+    }
+    next
+}
+
+// The most live neighbors a live pixel survives with
+fn survival(rule: Rule) -> usize {
+    match rule {
+        Rule::Maze      => 5,
+        Rule::Mazectric => 4
+    }
+}
+
+// Open a closed wall between every two regions that aren't connected yet, picked at random
+fn join_regions<R: Rng>(grid: &mut Grid, rng: &mut R) {
+    let height = grid.height;
+    let index = |(x, y): (usize, usize)| x * height + y;
+    let mut sets = Sets::new(grid.width * height);
+    let mut walls = Vec::new();
+    for x in 0 .. grid.width {
+        for y in 0 .. height {
+            let neighbors = [((x + 1, y), Direction::East), ((x, y + 1), Direction::South)];
+            for &(next, dir) in neighbors.iter().filter(|&&((nx, ny), _)| nx < grid.width && ny < height) {
+                if grid.has_wall(x, y, dir) {
+                    walls.push(((x, y), dir, next));
+                } else {
+                    sets.reparent(index((x, y)), index(next));
+                }
+            }
+        }
+    }
+    rng.shuffle(&mut walls);
+    for &((x, y), dir, next) in &walls {
+        if sets.reparent(index((x, y)), index(next)) {
+            grid.open(x, y, dir);
+        }
+    }
+}
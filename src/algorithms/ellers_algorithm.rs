@@ -1,15 +1,15 @@
-use std::collections::HashMap;
 use rand::Rng;
 use grid::{Direction, Grid};
 use super::Scan;
 
-struct EllersHelper {
-    // holds the sets for each cell in the current line
-    line: Vec<usize>,
-    counter: usize,
-    main: usize,
-    cross: usize,
-    scan: Scan
+// One finished line of an Eller's maze, across the direction the maze grows in.
+// true means the wall is closed.
+pub struct Line {
+    // The wall after each cell along the line. The last one is on the border, or leads
+    // back to the first cell when the lines wrap around.
+    pub side: Vec<bool>,
+    // The wall between each cell and the next line
+    pub next: Vec<bool>
 }
 
 // Based on http://weblog.jamisbuck.org/2010/12/29/maze-generation-eller-s-algorithm
@@ -17,9 +17,9 @@ struct EllersHelper {
 // 1. Create a vector of length width | height (depends on how we scan the maze).
 //      This vector will holds the set for each cell.
 // 2. Run over each row (column) of the maze.
-// 3. For each cell if it is closed - initialize its corresponding slot in the vector 
-//      to a new set. If it is not, leave the current value in (the first row (column)
-//      will always have all of its slots assigned).
+// 3. For each cell if it is not joined to the previous line - initialize its corresponding
+//      slot in the vector to a new set. If it is, leave the current value in (the first row
+//      (column) will always have all of its slots assigned).
 // 4. Randomly join adjacent cells, but only if they are not in the same set. When joining 
 //      adjacent cells, merge the cells of both sets into a single set, indicating that all 
 //      cells in both sets are now connected (there is a path that connects any two cells 
@@ -29,86 +29,131 @@ struct EllersHelper {
 //      thus connected will share the set of the cell above them.
 // 6. For the last row, join all adjacent cells that do not share a set, and omit the 
 //      vertical connections.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R, scan_dir: Scan) -> Grid {
-    let mut grid = Grid::new(width, height, true);
-    let mut helper = match scan_dir {
-        Scan::Horizontal => EllersHelper {
-            line: vec![0; width],
-            counter: 0,
-            main: height,
-            cross: width,
-            scan: Scan::Horizontal
-        },
-        Scan::Vertical   => EllersHelper {
-            line: vec![0; height],
+//
+// Generates an Eller's maze line after line. Only the sets of the current line are
+// kept, so an endless maze (when the number of lines isn't given) or a very long one
+// is generated in constant memory.
+pub struct EllersLines<R: Rng> {
+    rng: R,
+    // holds the sets for each cell in the current line
+    sets: Vec<usize>,
+    // whether each cell is joined to the line before it
+    joined: Vec<bool>,
+    counter: usize,
+    remaining: Option<usize>,
+    wrap: bool
+}
+
+impl<R: Rng> EllersLines<R> {
+    pub fn new(length: usize, lines: Option<usize>, rng: R) -> EllersLines<R> {
+        if length == 0 || lines == Some(0) {
+            panic!("The line length and the number of lines must be greater than 0.");
+        }
+        EllersLines {
+            rng,
+            sets: vec![0; length],
+            joined: vec![false; length],
             counter: 0,
-            main: width,
-            cross: height,
-            scan: Scan::Vertical
-        }                            
-    };
-    // Run over the maze line by line 
-    for i in 0 .. helper.main {
-        assign_sets(&mut grid, &mut helper, i);
-        carve_mainwise(&mut grid, &mut helper, i, rng);
-        // Make sure not to carve out of the maze
-        if i != helper.main - 1 {
-            carve_crosswise(&mut grid, &helper, i, rng);
+            remaining: lines,
+            wrap: false
         }
     }
-    grid
-}
 
-fn assign_sets(grid: &mut Grid, helper: &mut EllersHelper, i: usize) {      
-    for j in 0 .. helper.cross {
-        // Check how we're running over the maze 
-        let is_closed = match helper.scan {
-            Scan::Horizontal  => grid.cells[j][i].is_closed(),
-            Scan::Vertical    => grid.cells[i][j].is_closed()
-        };
-        // Initialize any closed cell to a new set
-        // (on the first line it will visit every cell)
-        if is_closed {
-            helper.line[j] = helper.counter;
-            helper.counter += 1;
+    // Join the last cell of every line to the first one as well, as around a cylinder
+    pub fn wrapping(mut self) -> EllersLines<R> {
+        self.wrap = true;
+        self
+    }
+
+    // Initialize any cell not joined to the previous line to a new set
+    // (on the first line it will visit every cell)
+    fn assign_sets(&mut self) {
+        for j in 0 .. self.sets.len() {
+            if !self.joined[j] {
+                self.sets[j] = self.counter;
+                self.counter += 1;
+            }
         }
     }
-}
 
-// Randomly merge adjacent cells from different sets
-fn carve_mainwise<R: Rng>(grid: &mut Grid, helper: &mut EllersHelper, i: usize, rng: &mut R) {
-    for j in 0 .. helper.cross - 1 {
-        let (old, new) = (helper.line[j], helper.line[j+1]);
-        // At the last line we must open every door between different sets
-        if old != new && (i == helper.main - 1 || rng.gen()) {
-            // merge sets
-            for set in &mut helper.line {
-                if *set == old { 
-                    *set = new;
+    // Randomly merge adjacent cells from different sets
+    fn carve_mainwise(&mut self, last: bool) -> Vec<bool> {
+        let length = self.sets.len();
+        let mut side = vec![true; length];
+        let walls = if self.wrap { length } else { length - 1 };
+        for (j, wall) in side.iter_mut().enumerate().take(walls) {
+            let (old, new) = (self.sets[j], self.sets[(j + 1) % length]);
+            // At the last line we must open every door between different sets
+            if old != new && (last || self.rng.gen()) {
+                // merge sets
+                for set in &mut self.sets {
+                    if *set == old {
+                        *set = new;
+                    }
                 }
+                *wall = false;
             }
-            match helper.scan {
-                Scan::Horizontal  => grid.open(j, i, Direction::East),
-                Scan::Vertical    => grid.open(i, j, Direction::South)
+        }
+        side
+    }
+
+    fn carve_crosswise(&mut self) -> Vec<bool> {
+        // Order the cells by their set
+        let sets = &self.sets;
+        let mut cells: Vec<usize> = (0 .. sets.len()).collect();
+        cells.sort_by_key(|&j| (sets[j], j));
+        // For each set choose a cell and carve to the next line
+        let mut next = vec![true; sets.len()];
+        for group in cells.chunk_by(|&a, &b| sets[a] == sets[b]) {
+            // Should always succeed in spite of the if let
+            if let Some(&j) = self.rng.choose(group) {
+                next[j] = false;
             }
         }
+        for (joined, &closed) in self.joined.iter_mut().zip(&next) {
+            *joined = !closed;
+        }
+        next
     }
 }
 
-fn carve_crosswise<R: Rng>(grid: &mut Grid, helper: &EllersHelper, i: usize, rng: &mut R) {
-    // Order the cells by their set 
-    let mut map = HashMap::new();
-    for (j, &set) in helper.line.iter().enumerate() {
-        let list = map.entry(set).or_insert_with(Vec::new);
-        list.push(j);
+impl<R: Rng> Iterator for EllersLines<R> {
+    type Item = Line;
+
+    fn next(&mut self) -> Option<Line> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        let last = self.remaining == Some(1);
+        self.remaining = self.remaining.map(|remaining| remaining - 1);
+        self.assign_sets();
+        let side = self.carve_mainwise(last);
+        let next = if last { vec![true; self.sets.len()] } else { self.carve_crosswise() };
+        Some(Line { side, next })
     }
-    // For each set choose a cell and carve south | east
-    for v in map.values() {
-        // Should always succeed in spite of the if let 
-        if let Some(&j) = rng.choose(v) {
-            match helper.scan {
-                Scan::Horizontal  => grid.open(j, i, Direction::South),
-                Scan::Vertical    => grid.open(i, j, Direction::East)
+}
+
+// The lines wrap around when the grid does along them, without turning upside down
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, scan_dir: Scan) {
+    let (main, cross, last) = match scan_dir {
+        Scan::Horizontal => (grid.height, grid.width, (grid.width - 1, 0, Direction::East)),
+        Scan::Vertical   => (grid.width, grid.height, (0, grid.height - 1, Direction::South))
+    };
+    let wraps = grid.cell_at(last.0, last.1, last.2) == Some((0, 0));
+    let lines = EllersLines::new(cross, Some(main), rng);
+    let lines = if wraps { lines.wrapping() } else { lines };
+    // Run over the maze line by line
+    for (i, line) in lines.enumerate() {
+        for j in 0 .. cross {
+            let (x, y, side, next) = match scan_dir {
+                Scan::Horizontal => (j, i, Direction::East, Direction::South),
+                Scan::Vertical   => (i, j, Direction::South, Direction::East)
+            };
+            if !line.side[j] {
+                grid.open(x, y, side);
+            }
+            if !line.next[j] {
+                grid.open(x, y, next);
             }
         }
     }
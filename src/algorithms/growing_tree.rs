@@ -1,59 +1,265 @@
-use rand::Rng;
-use grid::Grid;
-use super::{CellSelection, open_random_dir};
+use std::collections::BinaryHeap;
+use rand::{Rng, StdRng};
+use grid::Carve;
+use super::{CellSelection, Weights, open_random_dir};
+
+// Picks the cell growing tree carves from next. It gets the cells of the list by their
+// column, row and level, in the order they were added, and returns the index of one of
+// them. There's at least one. Between two picks either a cell is added at the end of the
+// list, or the picked one is taken out of it because it has no unvisited neighbor left,
+// and a selection that keeps state of its own is told so. A list of a single cell after
+// an addition is the start of a new maze.
+pub trait Selection<R: Rng = StdRng> {
+    fn select(&mut self, cells: &[(usize, usize, usize)], rng: &mut R) -> usize;
+
+    fn added(&mut self, _cells: &[(usize, usize, usize)]) {}
+
+    fn removed(&mut self, _index: usize) {}
+}
 
 // Based on http://weblog.jamisbuck.org/2011/1/27/maze-generation-growing-tree-algorithm
 // The algorithm:
 // 1. Start with a list of cell, initialized with a random cell from the maze.
 // 2. Choose a cell from the list (selection criteria given later) and open a passage
 //      to an unvisited neighbor cell.
-// 3. Add that neighbor to the list as well. 
+// 3. Add that neighbor to the list as well.
 // 4. If there are no unvisited neighbors, remove the cell from the list.
 // 5. Repeat 2-4 until the list is empty.
 // 6. Cell selection criteria can be newest cell, oldest, random, or a weighted mixture
 //      of the three.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R, selection_method: CellSelection) -> Grid {
-    // Start with a raw grid
-    let mut grid = Grid::new(width, height, true);
+pub fn carve<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, selection_method: CellSelection, weights: Weights) {
+    check(selection_method);
+    let mut selection: Box<dyn Selection<R>> = match selection_method {
+        CellSelection::Newest                        => Box::new(Newest),
+        CellSelection::Oldest                        => Box::new(Oldest),
+        CellSelection::Random                        => Box::new(Random),
+        CellSelection::Middle                        => Box::new(Middle),
+        CellSelection::NewestOldest(percent)         => Box::new(Either { first: Newest, second: Oldest, percent }),
+        CellSelection::NewestRandom(percent)         => Box::new(Either { first: Newest, second: Random, percent }),
+        CellSelection::OldestRandom(percent)         => Box::new(Either { first: Oldest, second: Random, percent }),
+        CellSelection::FarthestRandom(percent)       => {
+            Box::new(Either { first: Farthest::new(), second: Random, percent })
+        }
+        CellSelection::Mix { newest, oldest, random } => Box::new(Mix { newest, oldest, random })
+    };
+    run(grid, rng, &mut *selection, weights);
+}
+
+// Growing tree with any selection, the cells of the list are kept by their coordinates too
+pub fn run<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, selection: &mut dyn Selection<R>, weights: Weights) {
     // Choose a random starting point
-    let (x, y) = (rng.gen_range(0, width), rng.gen_range(0, height));
+    let start = grid.position(rng.gen_range(0, grid.size()));
     // Stack
-    let mut cells = Vec::new();
-    cells.push((x, y));
+    let mut cells = vec![start];
+    let mut coords = vec![grid.coordinates(start)];
+    selection.added(&coords);
     while !cells.is_empty() {
-        let (x, y, pos) = choose_cell(&cells, selection_method, rng);
-        match open_random_dir(&mut grid, x, y, rng) {
+        let pos = selection.select(&coords, rng);
+        if pos >= cells.len() {
+            panic!("The selection picked cell {} out of {}.", pos, cells.len());
+        }
+        match open_random_dir(grid, cells[pos], rng, weights) {
             // Found a cell to move
             Some(next) => {
                 cells.push(next);
+                coords.push(grid.coordinates(next));
+                selection.added(&coords);
             }
             // We have no unvisited cells around this cell
             None => {
                 cells.remove(pos);
+                coords.remove(pos);
+                selection.removed(pos);
             }
         }
     }
-    grid
 }
 
-fn choose_cell<R: Rng>(cells: &[(usize, usize)], selection_method: CellSelection, rng: &mut R) 
-    -> (usize, usize, usize) {
-    let np = cells.len() - 1;                           // newest
-    let (nx, ny) = cells[np];
-    let op = 0;                                         // oldest
-    let (ox, oy) = cells[op];
-    let rp = rng.gen_range(0, cells.len());             // random
-    let (rx, ry) = cells[rp];
-    let weight = rng.gen_range(1, 101);
+fn check(selection_method: CellSelection) {
     match selection_method {
-        CellSelection::Newest                         => (nx, ny, np),
-        CellSelection::Oldest                         => (ox, oy, op),
-        CellSelection::Random                         => (rx, ry, rp),
-        CellSelection::NewestOldest(p) if weight <= p => (nx, ny, np),
-        CellSelection::NewestOldest(_)                => (ox, oy, op),
-        CellSelection::NewestRandom(p) if weight <= p => (nx, ny, np), 
-        CellSelection::NewestRandom(_)                => (rx, ry, rp),
-        CellSelection::OldestRandom(p) if weight <= p => (ox, oy, op),
-        CellSelection::OldestRandom(_)                => (rx, ry, rp)
+        CellSelection::NewestOldest(p) | CellSelection::NewestRandom(p) | CellSelection::OldestRandom(p) |
+            CellSelection::FarthestRandom(p) if p > 100 => {
+            panic!("The percentage of {:?} is over 100.", selection_method);
+        }
+        CellSelection::Mix { newest: 0, oldest: 0, random: 0 } => {
+            panic!("A mix of cell selections needs a weight.");
+        }
+        _ => {}
+    }
+}
+
+// The newest cell of the list
+pub struct Newest;
+
+impl<R: Rng> Selection<R> for Newest {
+    fn select(&mut self, cells: &[(usize, usize, usize)], _: &mut R) -> usize {
+        cells.len() - 1
+    }
+}
+
+// The oldest cell of the list
+pub struct Oldest;
+
+impl<R: Rng> Selection<R> for Oldest {
+    fn select(&mut self, _: &[(usize, usize, usize)], _: &mut R) -> usize {
+        0
+    }
+}
+
+// A random cell of the list
+pub struct Random;
+
+impl<R: Rng> Selection<R> for Random {
+    fn select(&mut self, cells: &[(usize, usize, usize)], rng: &mut R) -> usize {
+        rng.gen_range(0, cells.len())
+    }
+}
+
+// Halfway between the oldest and the newest cell
+pub struct Middle;
+
+impl<R: Rng> Selection<R> for Middle {
+    fn select(&mut self, cells: &[(usize, usize, usize)], _: &mut R) -> usize {
+        (cells.len() - 1) / 2
+    }
+}
+
+// The first selection the given percentage of the time, and the second one otherwise
+pub struct Either<A, B> {
+    pub first: A,
+    pub second: B,
+    pub percent: usize
+}
+
+impl<R: Rng, A: Selection<R>, B: Selection<R>> Selection<R> for Either<A, B> {
+    fn select(&mut self, cells: &[(usize, usize, usize)], rng: &mut R) -> usize {
+        if rng.gen_range(1, 101) <= self.percent {
+            self.first.select(cells, rng)
+        } else {
+            self.second.select(cells, rng)
+        }
+    }
+
+    fn added(&mut self, cells: &[(usize, usize, usize)]) {
+        self.first.added(cells);
+        self.second.added(cells);
+    }
+
+    fn removed(&mut self, index: usize) {
+        self.first.removed(index);
+        self.second.removed(index);
+    }
+}
+
+// The newest, oldest or a random cell, in proportion to their weights
+pub struct Mix {
+    pub newest: usize,
+    pub oldest: usize,
+    pub random: usize
+}
+
+impl<R: Rng> Selection<R> for Mix {
+    fn select(&mut self, cells: &[(usize, usize, usize)], rng: &mut R) -> usize {
+        let draw = rng.gen_range(0, self.newest + self.oldest + self.random);
+        if draw < self.newest {
+            Newest.select(cells, rng)
+        } else if draw < self.newest + self.oldest {
+            Oldest.select(cells, rng)
+        } else {
+            Random.select(cells, rng)
+        }
+    }
+}
+
+// The cell farthest from the start in rows, columns and levels, the newest of them on a
+// tie. Every cell is numbered in the order it's added, and a Fenwick tree counts the
+// numbers still in the list, so the index of a cell is the count of those before it.
+pub struct Farthest {
+    start: (usize, usize, usize),
+    // The distance and number of every cell added, including those taken out since
+    heap: BinaryHeap<(usize, usize)>,
+    listed: Vec<bool>,
+    counts: Vec<usize>
+}
+
+impl Farthest {
+    pub fn new() -> Farthest {
+        Farthest { start: (0, 0, 0), heap: BinaryHeap::new(), listed: Vec::new(), counts: vec![0] }
+    }
+
+    // The tree grows by a node for every cell, which counts the cells it covers before
+    fn add(&mut self, (x, y, z): (usize, usize, usize)) {
+        let (sx, sy, sz) = self.start;
+        let number = self.listed.len();
+        self.heap.push((x.abs_diff(sx) + y.abs_diff(sy) + z.abs_diff(sz), number));
+        self.listed.push(true);
+        let i = number + 1;
+        let covered = self.prefix(i - 1) - self.prefix(i - (i & i.wrapping_neg()));
+        self.counts.push(covered + 1);
+    }
+
+    fn remove(&mut self, index: usize) {
+        let number = self.find(index);
+        self.listed[number] = false;
+        let mut i = number + 1;
+        while i < self.counts.len() {
+            self.counts[i] -= 1;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // How many cells numbered below this one are in the list
+    fn prefix(&self, number: usize) -> usize {
+        let (mut i, mut count) = (number, 0);
+        while i > 0 {
+            count += self.counts[i];
+            i -= i & i.wrapping_neg();
+        }
+        count
+    }
+
+    // The number of the cell at the index in the list
+    fn find(&self, index: usize) -> usize {
+        let (mut i, mut rest) = (0, index + 1);
+        let mut step = (self.counts.len() - 1).next_power_of_two();
+        while step > 0 {
+            if i + step < self.counts.len() && self.counts[i + step] < rest {
+                i += step;
+                rest -= self.counts[i];
+            }
+            step /= 2;
+        }
+        i
+    }
+}
+
+impl Default for Farthest {
+    fn default() -> Farthest {
+        Farthest::new()
+    }
+}
+
+impl<R: Rng> Selection<R> for Farthest {
+    fn select(&mut self, _: &[(usize, usize, usize)], _: &mut R) -> usize {
+        while let Some(&(_, number)) = self.heap.peek() {
+            if self.listed[number] {
+                return self.prefix(number);
+            }
+            self.heap.pop();
+        }
+        unreachable!("The list of cells is empty.")
+    }
+
+    fn added(&mut self, cells: &[(usize, usize, usize)]) {
+        let cell = cells[cells.len() - 1];
+        if cells.len() == 1 {
+            *self = Farthest::new();
+            self.start = cell;
+        }
+        self.add(cell);
+    }
+
+    fn removed(&mut self, index: usize) {
+        self.remove(index);
     }
 }
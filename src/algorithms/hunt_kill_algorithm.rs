@@ -1,6 +1,7 @@
+use std::collections::BTreeSet;
 use rand::Rng;
-use grid::{Direction, Grid};
-use super::open_random_dir;
+use grid::Carve;
+use super::{Hunt, Weights, open_random_dir};
 
 // Based on http://weblog.jamisbuck.org/2011/1/24/maze-generation-hunt-and-kill-algorithm
 // The algorithm:
@@ -10,45 +11,73 @@ use super::open_random_dir;
 //      cell that is adjacent to a visited one. 
 // 4. Once found, open a passage to the visited cell from the new cell and repeat steps 2-3
 // 5. If there are no more unvisited cell finish.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
-    // Start with a raw grid
-    let mut grid = Grid::new(width, height, true);
+pub fn carve<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, order: Hunt, weights: Weights) {
+    let order = scan(grid, order, rng);
+    // The place of every cell in the scan
+    let mut rank = vec![0; order.len()];
+    for (r, &i) in order.iter().enumerate() {
+        rank[i] = r;
+    }
+    // The unvisited cells next to a visited one, by their place in the scan. A hunt
+    // resumes at the first of them instead of scanning the maze from the start again.
+    let mut frontier = BTreeSet::new();
     // Choose a random starting point
-    let mut o = Some((rng.gen_range(0, width), rng.gen_range(0, height)));
-    while let Some((x, y)) = o {
+    let mut o = Some(grid.position(rng.gen_range(0, grid.size())));
+    while let Some(pos) = o {
+        visit(grid, pos, &rank, &mut frontier);
         // First case: continue where we're at
-        o = open_random_dir(&mut grid, x, y, rng);
+        o = open_random_dir(grid, pos, rng, weights);
         // Second case: Hunt for a new start
         if o.is_none() {
-            o = hunt(&mut grid, rng);
+            o = hunt(grid, rng, weights, &order, &frontier);
+        }
+    }
+}
+
+// The indices of the cells in the order the hunts run over them. Each level of a maze
+// is scanned on its own, from the lowest.
+fn scan<G: Carve, R: Rng>(grid: &G, order: Hunt, rng: &mut R) -> Vec<usize> {
+    let mut cells: Vec<usize> = (0 .. grid.size()).collect();
+    let coordinates = |i: usize| grid.coordinates(grid.position(i));
+    match order {
+        Hunt::Columns    => cells.sort_by_key(|&i| { let (x, y, z) = coordinates(i); (z, x, y) }),
+        Hunt::Rows       => cells.sort_by_key(|&i| { let (x, y, z) = coordinates(i); (z, y, x) }),
+        // Every other row runs backwards
+        Hunt::Serpentine => cells.sort_by_key(|&i| {
+            let (x, y, z) = coordinates(i);
+            (z, y, if y % 2 == 0 { x as isize } else { -(x as isize) })
+        }),
+        Hunt::Random     => rng.shuffle(&mut cells)
+    }
+    cells
+}
+
+// The cell is visited, so it leaves the frontier and its unvisited neighbours join it
+fn visit<G: Carve>(grid: &G, pos: G::Pos, rank: &[usize], frontier: &mut BTreeSet<usize>) {
+    frontier.remove(&rank[grid.index(pos)]);
+    for &dir in grid.directions() {
+        if let Some(next) = grid.neighbor(pos, dir) {
+            if grid.is_closed(next) {
+                frontier.insert(rank[grid.index(next)]);
+            }
         }
     }
-    grid
 }
 
-fn hunt<R: Rng>(grid: &mut Grid, rng: &mut R) -> Option<(usize, usize)> {
-    // Run over the maze, finds any unvisited cell with visited neighbours
-    let mut result = None;
-    let mut directions = [Direction::North, Direction::South, Direction::East, Direction::West];
-    rng.shuffle(&mut directions);        
-    'l: for x in 0 .. grid.width {
-        for y in 0 .. grid.height {
-            // Found an closed cell
-            if grid.cells[x][y].is_closed() {
-                // Find an open neighbour and carve.
-                // Or not, and we'll have to continue looking
-                for &dir in &directions {
-                    // get the position of the cell at direction dir and check that cell is unvisited
-                    if let Some((nx, ny)) = grid.cell_at(x, y, dir) {
-                        if !grid.cells[nx][ny].is_closed() {
-                            grid.open(x, y, dir);
-                            result = Some((x, y));
-                            break 'l;
-                        }
-                    }
-                }
+fn hunt<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, weights: Weights, order: &[usize], frontier: &BTreeSet<usize>)
+    -> Option<G::Pos> {
+    let mut directions = grid.directions().to_vec();
+    weights.shuffle(&mut directions, rng);
+    // The first unvisited cell with visited neighbours in the scan
+    let pos = grid.position(order[*frontier.first()?]);
+    // Open a passage to one of them
+    for &dir in &directions {
+        if let Some(next) = grid.neighbor(pos, dir) {
+            if !grid.is_closed(next) {
+                grid.open(pos, dir);
+                return Some(pos);
             }
         }
     }
-    result
+    None
 }
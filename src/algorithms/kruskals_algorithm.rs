@@ -1,23 +1,19 @@
 use rand::Rng;
-use grid::{Direction, Grid};
+use grid::{Carve, Direction};
 
-struct Sets {
-    width: usize,
+pub struct Sets {
     // each cell holds the location of its parent or itself if it is root
     cells: Vec<usize>
 }
 
 impl Sets {
-    fn new(width: usize, height: usize) -> Sets {
-        let size = width * height;
+    pub fn new(size: usize) -> Sets {
         Sets {
-            width: width,
-            cells: (0..size).map(|i| i).collect()
+            cells: (0..size).collect()
         }
     }
 
-    fn root(&mut self, x: usize, y: usize) -> usize {
-        let mut l = y * self.width + x;
+    pub fn root(&mut self, mut l: usize) -> usize {
         while self.cells[l] != l {
             // flatten the tree some
             self.cells[l] = self.cells[self.cells[l]];
@@ -28,10 +24,10 @@ impl Sets {
     }
 
     // attempt to join the two sets if they're not joined already
-    fn reparent(&mut self, fx: usize, fy: usize, sx: usize, sy: usize) -> bool {
+    pub fn reparent(&mut self, first: usize, second: usize) -> bool {
         let mut result = false;
-        let parent = self.root(fx, fy);
-        let child = self.root(sx, sy);
+        let parent = self.root(first);
+        let child = self.root(second);
         if parent != child {
             self.cells[child] = parent;
             result = true;
@@ -40,7 +36,7 @@ impl Sets {
     }
 }
 
-type Edge = (usize, usize, Direction);
+pub type Edge<P> = (P, Direction);
 
 // Based on http://weblog.jamisbuck.org/2011/1/3/maze-generation-kruskal-s-algorithm
 // The algorithm:
@@ -48,31 +44,34 @@ type Edge = (usize, usize, Direction);
 // 2. Sort all the edges in random order.
 // 3. Iterate over the set. For each edge, if the edge connects two disjoint set,
 //      join the sets and open a passage between them. otherwise, do nothing.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
-    // Start with a raw grid
-    let mut grid = Grid::new(width, height, true);
-    let mut sets = Sets::new(width, height);
-    let mut edges = collect_edges(width, height);
+pub fn carve<G: Carve, R: Rng>(grid: &mut G, rng: &mut R) {
+    let mut sets = Sets::new(grid.size());
+    let mut edges = collect_edges(grid);
     rng.shuffle(&mut edges);
     // Run over all the edges in the maze
-    for &(x, y, dir) in &edges {
+    for &(pos, dir) in &edges {
         // This should never fail
-        let (nx, ny) = grid.cell_at(x, y, dir).unwrap();
+        let next = grid.neighbor(pos, dir).unwrap();
         // If the two adjacent cells don't belong to the same set
-        if sets.reparent(x, y, nx, ny) {
+        if sets.reparent(grid.index(pos), grid.index(next)) {
             // Remove the edge between them
-            grid.open(x, y, dir);
+            grid.open(pos, dir);
         }
     }
-    grid
 }
 
-fn collect_edges(width: usize, height: usize) -> Vec<Edge> {
+// Each edge is collected once, from the cell on its north, west or lower side
+pub fn collect_edges<G: Carve>(grid: &G) -> Vec<Edge<G::Pos>> {
     let mut edges = Vec::new();
-    for y in 0 .. height - 1 {
-        for x in 0 .. width - 1 {
-            edges.push((x, y, Direction::South));
-            edges.push((x, y, Direction::East));
+    for i in 0 .. grid.size() {
+        let pos = grid.position(i);
+        for &dir in grid.directions() {
+            match dir {
+                Direction::South | Direction::East | Direction::Up if grid.neighbor(pos, dir).is_some() => {
+                    edges.push((pos, dir));
+                }
+                _ => {}
+            }
         }
     }
     edges
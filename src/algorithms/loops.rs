@@ -0,0 +1,85 @@
+use rand::Rng;
+use rand::seq::sample_indices;
+use grid::{Direction, Grid};
+use solver::{count_solutions, entry_points, shortest_path};
+
+// The number of walls weighed against each other for every loop
+const SAMPLES: usize = 10;
+
+// Extra loops knocked through the walls of a carved maze
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Loops {
+    // Exactly this many loops, unless the maze runs out of walls
+    Count(usize),
+    // Loops until there are at least this many ways from the entry to the exit
+    Solutions(usize)
+}
+
+// A loop is only worth it when it's a real alternative, so out of a few random walls the
+// one between the cells that are the farthest apart in the maze is opened: the long way
+// round between them becomes a single step. To reach a number of solutions, a loop that
+// doesn't add a way from the entry to the exit is closed again.
+pub fn insert<R: Rng>(grid: &mut Grid, rng: &mut R, loops: Loops) {
+    let mut walls = closed_walls(grid);
+    match loops {
+        Loops::Count(count) => {
+            for _ in 0 .. count {
+                match farthest(grid, rng, &mut walls) {
+                    Some((x, y, dir)) => grid.open(x, y, dir),
+                    None              => break
+                }
+            }
+        }
+        Loops::Solutions(target) => {
+            let (from, to) = match entry_points(grid)[..] {
+                [from, to, ..] => (from, to),
+                _              => panic!("Loops can only add solutions to a maze with an entry and an exit.")
+            };
+            let mut solutions = count_solutions(grid, from, to, target);
+            while solutions < target {
+                let (x, y, dir) = match farthest(grid, rng, &mut walls) {
+                    Some(wall) => wall,
+                    None       => break
+                };
+                grid.open(x, y, dir);
+                match count_solutions(grid, from, to, target) {
+                    more if more > solutions => solutions = more,
+                    _                        => grid.close(x, y, dir)
+                }
+            }
+        }
+    }
+}
+
+// The east and south walls between two cells, leaving out the crossings so they stay
+// whole
+fn closed_walls(grid: &Grid) -> Vec<(usize, usize, Direction)> {
+    let mut walls = Vec::new();
+    for x in 0 .. grid.width {
+        for y in 0 .. grid.height {
+            for &dir in &[Direction::East, Direction::South] {
+                match grid.cell_at(x, y, dir) {
+                    Some(next) if next != (x, y) && grid.has_wall(x, y, dir) &&
+                        grid.crossing(x, y).is_none() && grid.crossing(next.0, next.1).is_none() => {
+                        walls.push((x, y, dir));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    walls
+}
+
+// Take the wall between the cells the farthest apart out of a few random ones
+fn farthest<R: Rng>(grid: &Grid, rng: &mut R, walls: &mut Vec<(usize, usize, Direction)>)
+    -> Option<(usize, usize, Direction)> {
+    let distance = |&(x, y, dir): &(usize, usize, Direction)| {
+        let next = grid.cell_at(x, y, dir).unwrap();
+        shortest_path(grid, (x, y), next).map_or(usize::MAX, |path| path.len())
+    };
+    let best = sample_indices(rng, walls.len(), SAMPLES.min(walls.len())).into_iter()
+        .max_by_key(|&i| distance(&walls[i]))?;
+    Some(walls.swap_remove(best))
+}
@@ -1,7 +1,9 @@
-use rand::Rng;
-use grid::{Direction, Grid};
+use rand::{Rng, StdRng};
+use grid::{Carve, Direction, Grid, PLANAR};
+use layered::LayeredGrid;
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Algorithm {
     BinaryTree(Bias),
     EllersAlgorithm(Scan),
@@ -11,20 +13,66 @@ pub enum Algorithm {
     GrowingTree(CellSelection),
     SidewinderAlgorithm(Scan),
     KruskalsAlgorithm,
-    RecursiveDivision
+    RecursiveDivision,
+    // The parameter is the largest number of cells left open as a room, 1 leaves none
+    BlobbyDivision(usize),
+    CellularAutomaton(Automaton),
+    // The parameter is the number of times the origin moves for every cell
+    OriginShift(usize),
+    // The parameter is the percentage of inner cells tried as crossings
+    WeaveKruskalsAlgorithm(usize),
+    // Recursive division with rooms, off-center splits or extra passages
+    RecursiveDivisionWith(Division),
+    // Hunt and kill with the order it hunts in
+    HuntKillAlgorithmWith(Hunt),
+    // Prim's with the costs it grows by
+    PrimsAlgorithmWith(Prims)
 }
 
+// The cell growing tree carves from next. A mix of two picks the first one the given
+// percentage of the time, and the second one otherwise.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CellSelection {
     Newest,
     Oldest,
     Random,
+    // Halfway between the oldest and the newest cell
+    Middle,
     NewestOldest(usize),
     NewestRandom(usize),
-    OldestRandom(usize)
+    OldestRandom(usize),
+    // The cell farthest from the start, so passages run outwards
+    FarthestRandom(usize),
+    // The newest, oldest or a random cell, in proportion to their weights
+    Mix { newest: usize, oldest: usize, random: usize }
 }
 
+// The order hunt and kill runs over the cells in, when it hunts for a new start
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Hunt {
+    Columns,
+    Rows,
+    Random,
+    // Row by row, every other one backwards
+    Serpentine
+}
+
+// Which cell Prim's algorithm adds to the maze next
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Prims {
+    // A random cell next to the maze
+    Simplified,
+    // True Prim's, every cell has a random cost and the cheapest one next to the maze is added
+    Cells,
+    // True Prim's, every passage has a random cost and the cheapest one out of the maze is carved
+    Edges
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Bias {
     Northeast,
     Northwest,
@@ -33,36 +81,207 @@ pub enum Bias {
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Scan {
     Horizontal,
     Vertical
 }
 
+// How recursive division splits the maze. The default divides it down to corridors,
+// with a wall anywhere in an area and a single passage through it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Division {
+    // Areas no wider and no higher than this are left open as rooms
+    pub room_size: usize,
+    // The percentage walls are pulled towards the middle of an area, 100 always
+    // splits it in two equal halves
+    pub centering: usize,
+    // The number of passages through each wall
+    pub passages: usize
+}
+
+impl Default for Division {
+    fn default() -> Division {
+        Division { room_size: 1, centering: 0, passages: 1 }
+    }
+}
+
+// A Life-like rule. A dead pixel comes to life with 3 live neighbors, and a live one
+// survives with 1 to 5 of them (B3/S12345) or 1 to 4 (B3/S1234).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Rule {
+    // B3/S12345, winding corridors with many dead ends
+    Maze,
+    // B3/S1234, longer and straighter corridors
+    Mazectric
+}
+
+// How the cellular automaton grows the walls
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Automaton {
+    pub rule: Rule,
+    // The number of generations to run, it stops earlier once nothing changes
+    pub steps: usize,
+    // The percentage of the walls and posts that are alive at the start
+    pub density: usize
+}
+
+impl Default for Automaton {
+    fn default() -> Automaton {
+        Automaton { rule: Rule::Maze, steps: 100, density: 50 }
+    }
+}
+
+// How likely the generators are to carve towards each direction, relative to the
+// other directions. A direction with no weight is only carved when there's no other
+// way, so the maze stays perfect.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Weights {
+    pub north: usize,
+    pub south: usize,
+    pub east: usize,
+    pub west: usize,
+    // Up and down the stairs of a maze with several levels
+    pub stairs: usize
+}
+
+impl Weights {
+    pub fn uniform() -> Weights {
+        Weights { north: 1, south: 1, east: 1, west: 1, stairs: 1 }
+    }
+
+    // A preference for east-west passages over north-south ones, or the other way around
+    pub fn axes(horizontal: usize, vertical: usize) -> Weights {
+        Weights { north: vertical, south: vertical, east: horizontal, west: horizontal, stairs: 1 }
+    }
+
+    pub fn get(&self, dir: Direction) -> usize {
+        match dir {
+            Direction::North => self.north,
+            Direction::South => self.south,
+            Direction::East  => self.east,
+            Direction::West  => self.west,
+            Direction::Up | Direction::Down => self.stairs
+        }
+    }
+
+    fn is_uniform(&self, directions: &[Direction]) -> bool {
+        directions.iter().all(|&dir| self.get(dir) == self.get(directions[0]))
+    }
+
+    // Order the directions at random, the heavier ones tend to come first. Uniform
+    // weights shuffle them as before, so a seed gives the same maze as it used to.
+    fn shuffle<R: Rng>(&self, directions: &mut [Direction], rng: &mut R) {
+        if self.is_uniform(directions) {
+            rng.shuffle(directions);
+            return;
+        }
+        for i in 0 .. directions.len() {
+            let total: usize = directions[i ..].iter().map(|&dir| self.get(dir)).sum();
+            if total == 0 {
+                rng.shuffle(&mut directions[i ..]);
+                break;
+            }
+            // Pick the next direction with a chance in proportion to its weight
+            let (mut draw, mut j) = (rng.gen_range(0, total), i);
+            while draw >= self.get(directions[j]) {
+                draw -= self.get(directions[j]);
+                j += 1;
+            }
+            directions.swap(i, j);
+        }
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights::uniform()
+    }
+}
+
+// A maze generator of your own, for Maze::generator. It gets a grid with all of its
+// walls closed and the maze's random number generator, so the same seed still gives
+// the same maze. The entry points are opened after it's done.
+pub trait Generator {
+    fn carve(&mut self, grid: &mut Grid, rng: &mut StdRng);
+}
+
+// Carve a closed grid with one of the algorithms. The weights bias the recursive
+// backtracking, hunt and kill, Prim's, growing tree and recursive division algorithms.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, algorithm: Algorithm, weights: Weights) {
+    match algorithm {
+        Algorithm::BinaryTree(bias)            => binary_tree::carve(grid, rng, bias),
+        Algorithm::EllersAlgorithm(scan)       => ellers_algorithm::carve(grid, rng, scan),
+        Algorithm::RecursiveBacktracking       => recursive_backtracking::carve(grid, rng, weights),
+        Algorithm::HuntKillAlgorithm           => hunt_kill_algorithm::carve(grid, rng, Hunt::Columns, weights),
+        Algorithm::PrimsAlgorithm              => prims_algorithm::carve(grid, rng, Prims::Simplified, weights),
+        Algorithm::GrowingTree(cell_selection) => growing_tree::carve(grid, rng, cell_selection, weights),
+        Algorithm::SidewinderAlgorithm(scan)   => sidewinder_algorithm::carve(grid, rng, scan),
+        Algorithm::KruskalsAlgorithm           => kruskals_algorithm::carve(grid, rng),
+        Algorithm::RecursiveDivision           => {
+            recursive_division::carve(grid, rng, Division::default(), weights)
+        }
+        Algorithm::BlobbyDivision(room_size)   => blobby_division::carve(grid, rng, room_size),
+        Algorithm::CellularAutomaton(automaton) => cellular_automaton::carve(grid, rng, automaton),
+        Algorithm::OriginShift(steps)          => origin_shift::carve(grid, rng, steps),
+        Algorithm::WeaveKruskalsAlgorithm(d)   => weave_kruskals_algorithm::carve(grid, rng, d),
+        Algorithm::RecursiveDivisionWith(division) => recursive_division::carve(grid, rng, division, weights),
+        Algorithm::HuntKillAlgorithmWith(order) => hunt_kill_algorithm::carve(grid, rng, order, weights),
+        Algorithm::PrimsAlgorithmWith(prims)   => prims_algorithm::carve(grid, rng, prims, weights)
+    }
+}
+
+// Sides that wrap around have no border to open, so a torus has no entry points
 pub fn choose_entry_points<R: Rng>(grid: &mut Grid, rng: &mut R) {
-    let mut directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+    let mut directions = borders(grid);
     rng.shuffle(&mut directions);
     for &dir in directions.iter().take(2) {
-        let (x, y) = match dir {
-            Direction::North => (rng.gen_range(0, grid.width), 0),
-            Direction::South => (rng.gen_range(0, grid.width), grid.height - 1),
-            Direction::East  => (grid.width - 1, rng.gen_range(0, grid.height)),
-            Direction::West  => (0, rng.gen_range(0, grid.height))
-        };
-        grid.open(x, y, dir);
+        open_border(grid, dir, rng);
     }
 } 
 
+// The entry is on the bottom level and the exit on the top one
+pub fn choose_layered_entry_points<R: Rng>(grid: &mut LayeredGrid, rng: &mut R) {
+    let top = grid.depth() - 1;
+    for &z in &[0, top] {
+        let directions = borders(&grid.levels[z]);
+        if let Some(&dir) = rng.choose(&directions) {
+            open_border(&mut grid.levels[z], dir, rng);
+        }
+    }
+}
+
+fn borders(grid: &Grid) -> Vec<Direction> {
+    PLANAR.iter().cloned().filter(|&dir| grid.has_border(dir)).collect()
+}
+
+// open the outer wall of a random cell on the dir side of the grid
+fn open_border<R: Rng>(grid: &mut Grid, dir: Direction, rng: &mut R) {
+    let (x, y) = match dir {
+        Direction::North => (rng.gen_range(0, grid.width), 0),
+        Direction::South => (rng.gen_range(0, grid.width), grid.height - 1),
+        Direction::East  => (grid.width - 1, rng.gen_range(0, grid.height)),
+        Direction::West  => (0, rng.gen_range(0, grid.height)),
+        Direction::Up | Direction::Down => return
+    };
+    grid.open(x, y, dir);
+}
+
  // open passage to a closed  adjacent cell in a random direction
-fn open_random_dir<R: Rng>(grid: &mut Grid, x: usize, y: usize, rng: &mut R) -> Option<(usize, usize)> {
+fn open_random_dir<G: Carve, R: Rng>(grid: &mut G, pos: G::Pos, rng: &mut R, weights: Weights) -> Option<G::Pos> {
     let mut result = None;
-    let mut directions = [Direction::North, Direction::South, Direction::East, Direction::West];
-    rng.shuffle(&mut directions);
+    let mut directions = grid.directions().to_vec();
+    weights.shuffle(&mut directions, rng);
     for &dir in &directions {
         // get the position of the cell at direction dir and check that cell is unvisited
-        if let Some((nx, ny)) = grid.cell_at(x, y, dir) {
-            if grid.cells[nx][ny].is_closed() {
-                grid.open(x, y, dir);
-                result = Some((nx, ny));
+        if let Some(next) = grid.neighbor(pos, dir) {
+            if grid.is_closed(next) {
+                grid.open(pos, dir);
+                result = Some(next);
                 break;
             }
         }
@@ -79,3 +298,10 @@ pub mod growing_tree;
 pub mod sidewinder_algorithm;
 pub mod kruskals_algorithm;
 pub mod recursive_division;
+pub mod blobby_division;
+pub mod cellular_automaton;
+pub mod origin_shift;
+pub mod weave_kruskals_algorithm;
+pub mod tiled;
+pub mod regions;
+pub mod loops;
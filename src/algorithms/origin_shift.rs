@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use rand::Rng;
+use grid::{Direction, Grid, PLANAR};
+
+// Based on https://github.com/CaptainLuma/New-Maze-Generating-Algorithm
+// The algorithm:
+// 1. Start with any perfect maze, and pick one of its cells as the origin. Every other
+//      cell points to the passage that leads towards the origin, so the maze is a tree
+//      directed to the origin.
+// 2. Move the origin to a random neighbor. The old origin now points to the new one,
+//      through the wall between them, which is opened.
+// 3. The new origin doesn't point anywhere any more, and the wall it used to point
+//      through is closed.
+// 4. Repeat 2-3. The maze is still perfect after every step.
+//
+// Keeps shifting the origin of an existing maze, so the maze slowly changes. The outer
+// walls are never touched, so the entry points stay where they are.
+pub struct OriginShift<R: Rng> {
+    rng: R,
+    grid: Grid,
+    // The direction every cell points to, the origin points nowhere
+    parents: Vec<Option<Direction>>,
+    origin: (usize, usize)
+}
+
+impl<R: Rng> OriginShift<R> {
+    // The maze must be perfect and without crossings. The origin starts at a random cell.
+    pub fn new(grid: Grid, mut rng: R) -> OriginShift<R> {
+        if grid.has_crossings() {
+            panic!("Origin shift can't change a maze with crossings.");
+        }
+        let origin = (rng.gen_range(0, grid.width), rng.gen_range(0, grid.height));
+        let parents = parents(&grid, origin)
+            .unwrap_or_else(|| panic!("Origin shift can only change a perfect maze."));
+        OriginShift { rng, grid, parents, origin }
+    }
+
+    // Move the origin the given number of times
+    pub fn step(&mut self, steps: usize) {
+        for _ in 0 .. steps {
+            shift(&mut self.grid, &mut self.parents, &mut self.origin, &mut self.rng);
+        }
+    }
+
+    pub fn origin(&self) -> (usize, usize) {
+        self.origin
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn into_grid(self) -> Grid {
+        self.grid
+    }
+}
+
+// Start with every line leading east, and the last column leading south to the origin in
+// the bottom right corner. Then shift the origin the given number of times for every cell.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, steps: usize) {
+    let (width, height) = (grid.width, grid.height);
+    let mut parents = vec![None; width * height];
+    for x in 0 .. width {
+        for y in 0 .. height {
+            let dir = match (x + 1 < width, y + 1 < height) {
+                (true, _)      => Direction::East,
+                (false, true)  => Direction::South,
+                (false, false) => continue
+            };
+            grid.open(x, y, dir);
+            parents[x * height + y] = Some(dir);
+        }
+    }
+    let mut origin = (width - 1, height - 1);
+    for _ in 0 .. steps * width * height {
+        shift(grid, &mut parents, &mut origin, rng);
+    }
+}
+
+fn shift<R: Rng>(grid: &mut Grid, parents: &mut [Option<Direction>], origin: &mut (usize, usize), rng: &mut R) {
+    let (x, y) = *origin;
+    // A cell can be its own neighbor on a narrow wrapping grid
+    let directions: Vec<Direction> = PLANAR.iter().cloned()
+        .filter(|&dir| grid.cell_at(x, y, dir).is_some_and(|next| next != (x, y)))
+        .collect();
+    let dir = match rng.choose(&directions) {
+        Some(&dir) => dir,
+        None       => return
+    };
+    let (nx, ny) = grid.cell_at(x, y, dir).unwrap();
+    // Close the way out of the new origin first, it may be the wall to the old one
+    if let Some(out) = parents[nx * grid.height + ny].take() {
+        grid.close(nx, ny, out);
+    }
+    grid.open(x, y, dir);
+    parents[x * grid.height + y] = Some(dir);
+    *origin = (nx, ny);
+}
+
+// The direction from every cell towards the origin, found with a breadth first search.
+// None if the maze isn't perfect.
+fn parents(grid: &Grid, origin: (usize, usize)) -> Option<Vec<Option<Direction>>> {
+    let index = |(x, y): (usize, usize)| x * grid.height + y;
+    let mut parents = vec![None; grid.width * grid.height];
+    let mut seen = vec![false; grid.width * grid.height];
+    let mut passages = 0;
+    let mut queue = VecDeque::new();
+    seen[index(origin)] = true;
+    queue.push_back(origin);
+    while let Some((x, y)) = queue.pop_front() {
+        for &dir in &PLANAR {
+            let next = match grid.passage(x, y, dir) {
+                Some(next) => next,
+                None       => continue
+            };
+            passages += 1;
+            if !seen[index(next)] {
+                seen[index(next)] = true;
+                // The same passage, seen from the other side
+                parents[index(next)] = PLANAR.iter().cloned()
+                    .find(|&back| grid.passage(next.0, next.1, back) == Some((x, y)));
+                queue.push_back(next);
+            }
+        }
+    }
+    // Every passage is counted from both of its sides
+    let perfect = seen.iter().all(|&seen| seen) && passages == 2 * (seen.len() - 1);
+    if perfect {
+        Some(parents)
+    } else {
+        None
+    }
+}
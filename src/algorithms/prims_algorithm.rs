@@ -1,8 +1,11 @@
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use rand::Rng;
-use grid::{Direction, Grid};
+use grid::Carve;
+use super::{Prims, Weights};
 
-type Set = HashSet<(usize, usize)>;
+// The costs true Prim's draws from
+const COSTS: u64 = 1 << 32;
 
 // Based on http://weblog.jamisbuck.org/2011/1/10/maze-generation-prim-s-algorithm
 // The algorithm:
@@ -14,44 +17,136 @@ type Set = HashSet<(usize, usize)>;
 //      open a passage to the current cell.
 // 5. Remove the current cell from the frontier set and add it to the done set.
 // 6. The algorithm ends when the frontier set is empty.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
-    // Start with a raw grid
-    let mut grid = Grid::new(width, height, true);
-    let mut done = HashSet::new();
-    let mut frontier = HashSet::new();
+// That's the simplified version, which takes a random cell of the frontier in 3. True
+// Prim's gives every cell or every passage a random cost, and takes the cheapest one
+// instead. It's a minimum spanning tree, with many more short dead ends.
+pub fn carve<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, prims: Prims, weights: Weights) {
+    match prims {
+        Prims::Simplified => simplified(grid, rng, weights),
+        Prims::Cells      => cheapest_cells(grid, rng, weights),
+        Prims::Edges      => cheapest_edges(grid, rng, weights)
+    }
+}
+
+fn simplified<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, weights: Weights) {
+    let mut done = vec![false; grid.size()];
+    let mut frontier = Frontier::new(grid.size());
     // Start at a random point in the maze
-    let start_at = (rng.gen_range(0, width), rng.gen_range(0, height));
-    frontier.insert(start_at);
-    while !frontier.is_empty() {
-        let (x, y) = choose(&mut frontier, rng);
-        connect(&mut grid, &mut frontier, rng, &mut done, x, y);
+    let start_at = grid.position(rng.gen_range(0, grid.size()));
+    frontier.insert(start_at, grid.index(start_at));
+    while let Some(pos) = frontier.choose(rng) {
+        frontier.remove(grid.index(pos));
+        connect(grid, rng, &done, pos, weights);
+        done[grid.index(pos)] = true;
+        for &dir in grid.directions() {
+            // It may already belong to the frontier set but we don't care
+            if let Some(p) = grid.neighbor(pos, dir).filter(|&p| !done[grid.index(p)]) {
+                frontier.insert(p, grid.index(p));
+            }
+        }
+    }
+}
+
+// Every cell costs a random amount, and the cheapest cell next to the maze is added to it
+fn cheapest_cells<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, weights: Weights) {
+    let costs: Vec<u64> = (0 .. grid.size()).map(|_| rng.gen_range(0, COSTS)).collect();
+    let mut done = vec![false; grid.size()];
+    let mut queued = vec![false; grid.size()];
+    let mut heap = BinaryHeap::new();
+    let start = rng.gen_range(0, grid.size());
+    queued[start] = true;
+    heap.push(Reverse((costs[start], start)));
+    while let Some(Reverse((_, index))) = heap.pop() {
+        let pos = grid.position(index);
+        connect(grid, rng, &done, pos, weights);
+        done[index] = true;
+        for &dir in grid.directions() {
+            if let Some(next) = grid.neighbor(pos, dir).map(|p| grid.index(p)).filter(|&next| !queued[next]) {
+                queued[next] = true;
+                heap.push(Reverse((costs[next], next)));
+            }
+        }
     }
-    grid
 }
 
-fn choose<R: Rng>(set: &mut Set, rng: &mut R) -> (usize, usize) {
-    let idx = rng.gen_range(0, set.len());
-    *set.iter().nth(idx).unwrap()
+// Every passage costs a random amount, and the cheapest passage out of the maze is carved.
+// A heavier direction tends to cost less.
+fn cheapest_edges<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, weights: Weights) {
+    let mut done = vec![false; grid.size()];
+    let mut heap = BinaryHeap::new();
+    let start = rng.gen_range(0, grid.size());
+    add_edges(grid, rng, &mut done, &mut heap, start, weights);
+    while let Some(Reverse((_, index, d))) = heap.pop() {
+        let (pos, dir) = (grid.position(index), grid.directions()[d]);
+        let next = match grid.neighbor(pos, dir) {
+            Some(next) if !done[grid.index(next)] => next,
+            _ => continue
+        };
+        grid.open(pos, dir);
+        add_edges(grid, rng, &mut done, &mut heap, grid.index(next), weights);
+    }
+}
+
+// Add a cell to the maze, and the passages out of it to the heap, by cost, cell and direction
+fn add_edges<G: Carve, R: Rng>(grid: &G, rng: &mut R, done: &mut [bool],
+    heap: &mut BinaryHeap<Reverse<(u64, usize, usize)>>, index: usize, weights: Weights) {
+    done[index] = true;
+    let pos = grid.position(index);
+    for (d, &dir) in grid.directions().iter().enumerate() {
+        if grid.neighbor(pos, dir).is_some_and(|next| !done[grid.index(next)]) {
+            let draw = rng.gen_range(0, COSTS);
+            // A direction with no weight costs more than any other
+            let cost = match weights.get(dir) as u64 {
+                0      => COSTS + draw,
+                weight => draw / weight
+            };
+            heap.push(Reverse((cost, index, d)));
+        }
+    }
 }
 
-fn connect<R: Rng>(grid: &mut Grid, frontier: &mut Set, rng: &mut R, done: &mut Set, x: usize, y: usize) {
-    let mut connected = false;
-    let mut directions = [Direction::North, Direction::South, Direction::East, Direction::West];
-    rng.shuffle(&mut directions);
+// Open a passage from the cell to a random neighbor that's done, if there's one
+fn connect<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, done: &[bool], pos: G::Pos, weights: Weights) {
+    let mut directions = grid.directions().to_vec();
+    // The first done cell found is the one the passage leads to
+    weights.shuffle(&mut directions, rng);
     for &dir in &directions {
-        if let Some(p) = grid.cell_at(x, y, dir) {
-            // The cell doesn't belong to the done set.
-            // It may already belong to the frontier set but we don't care                
-            if !done.contains(&p) {
-                frontier.insert(p);
-            // This is the first cell that belong the done set that we've
-            // found so we'll open a passage between it and the current cell                
-            } else if !connected {
-                grid.open(x, y, dir);
-                connected = true;
+        if grid.neighbor(pos, dir).is_some_and(|p| done[grid.index(p)]) {
+            grid.open(pos, dir);
+            break;
+        }
+    }
+}
+
+// The frontier set, with the slot of every cell in it so a cell is removed in constant time
+struct Frontier<P> {
+    cells: Vec<(P, usize)>,
+    slots: Vec<Option<usize>>
+}
+
+impl<P: Copy> Frontier<P> {
+    fn new(size: usize) -> Frontier<P> {
+        Frontier { cells: Vec::new(), slots: vec![None; size] }
+    }
+
+    fn choose<R: Rng>(&self, rng: &mut R) -> Option<P> {
+        rng.choose(&self.cells).map(|&(pos, _)| pos)
+    }
+
+    fn insert(&mut self, pos: P, index: usize) {
+        if self.slots[index].is_none() {
+            self.slots[index] = Some(self.cells.len());
+            self.cells.push((pos, index));
+        }
+    }
+
+    // The last cell takes the slot of the removed one
+    fn remove(&mut self, index: usize) {
+        if let Some(slot) = self.slots[index].take() {
+            self.cells.swap_remove(slot);
+            if let Some(&(_, moved)) = self.cells.get(slot) {
+                self.slots[moved] = Some(slot);
             }
         }
     }
-    frontier.remove(&(x, y));
-    done.insert((x, y));
 }
@@ -1,6 +1,6 @@
 use rand::Rng;
-use grid::Grid;
-use super::open_random_dir;
+use grid::Carve;
+use super::{Weights, open_random_dir};
 
 // Based on http://weblog.jamisbuck.org/2010/12/27/maze-generation-recursive-backtracking
 // The algorithm:
@@ -11,18 +11,16 @@ use super::open_random_dir;
 // 3. If all adjacent cells have been visited, back up to the last cell that is closed
 //      and repeat step 2.
 // 4. The algorithm ends when the process has backed all the way up to the starting point.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
-    // Start with a raw grid
-    let mut grid = Grid::new(width, height, true);
+pub fn carve<G: Carve, R: Rng>(grid: &mut G, rng: &mut R, weights: Weights) {
     let mut indices = Vec::new();
     // Choose a random starting choose_entry_points
-    let o = (rng.gen_range(0, width), rng.gen_range(0, height));        
+    let o = grid.position(rng.gen_range(0, grid.size()));
     // Start with the first location (picked at random)
     indices.push(o);
     // Walk in random opening passages in the maze but only to unvisitied cells
     while !indices.is_empty() {
-        let &(x, y) = indices.last().unwrap();
-        match open_random_dir(&mut grid, x, y, rng) {
+        let &pos = indices.last().unwrap();
+        match open_random_dir(grid, pos, rng, weights) {
             // Found a cell to move
             Some(next) => {                    
                 indices.push(next);
@@ -33,5 +31,4 @@ pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
             }
         }
     }
-    grid
 }
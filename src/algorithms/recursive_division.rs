@@ -1,5 +1,7 @@
 use rand::Rng;
-use grid::{Direction, Grid};   
+use rand::seq::sample_indices;
+use grid::{Direction, Grid};
+use super::{Division, Weights};
 
 struct Line {
     fixed: usize,
@@ -22,61 +24,99 @@ struct Area {
 //      halves.
 // 3. Randomly open a passage through the previously created line.
 // 4. For each of the two resulting areas recursively repeat step 2-4.
-// 5. Exit case is when the area is a single corridor.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R) -> Grid {
-    // Start with a raw grid
-    let mut grid = Grid::new(width, height, false);
+// 5. Exit case is when the area is a single corridor, or small enough to be left as a room.
+// Rooms and more than one passage per line add loops, so the maze is only perfect with
+// the default division.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, division: Division, weights: Weights) {
+    let (width, height) = (grid.width, grid.height);
+    // Empty the grid, but keep it enclosed by its outer walls
+    for x in 0 .. width {
+        for y in 0 .. height {
+            if x < width - 1 {
+                grid.open(x, y, Direction::East);
+            }
+            if y < height - 1 {
+                grid.open(x, y, Direction::South);
+            }
+        }
+    }
     // Stack of areas to divide
     let mut stack = Vec::new();
     // Start with the whole area of the maze
     stack.push(Area { xs: 0, xe: width - 1, ys: 0, ye: height - 1 });
     while let Some(a) = stack.pop() {
-        if let Some((b, c)) = bisect(&mut grid, a, rng) {
+        if let Some((b, c)) = bisect(grid, a, rng, division, weights) {
             stack.push(b);
             stack.push(c);
         }
     }
-    grid
 }
 
-fn bisect<R: Rng>(grid: &mut Grid, a: Area, rng: &mut R) -> Option<(Area, Area)> {
-    let mut result = None;
-    // Exit case: when we're down to a corridor in the maze
-    if a.xe - a.xs > 1 && a.ye - a.ys > 1 {
-        let (x, y) = (rng.gen_range(a.xs, a.xe), rng.gen_range(a.ys, a.ye));
-        let &dir = rng.choose(&[Direction::South, Direction::East]).unwrap();
-        let line;
-        // Based on the direction we define the line (horizontal or vertical)
-        // and the areas it delineate
-        match dir {
-            Direction::South => {
-                line = Line { fixed: y, low: a.xs, high: a.xe, dir: Direction::South };
-                result = Some((Area { ye: y, ..a }, Area { ys: y + 1, ..a }));
-            }
-            Direction::East => {
-                line = Line { fixed: x, low: a.ys, high: a.ye, dir: Direction::East };
-                result = Some((Area { xe: x, ..a }, Area { xs: x + 1, ..a }));
-            }
-            _ => { panic!("Unreachable fork in recursive_division::bisect") }
-        };
-        // Divide the area into two areas with a single passage between them
-        close_line(grid, line);
-        grid.open(x, y, dir);
+fn bisect<R: Rng>(grid: &mut Grid, a: Area, rng: &mut R, division: Division, weights: Weights) -> Option<(Area, Area)> {
+    let (width, height) = (a.xe - a.xs + 1, a.ye - a.ys + 1);
+    let room_size = division.room_size.max(1);
+    // Exit case: when we're down to a corridor, or to a room
+    if width == 1 || height == 1 || (width <= room_size && height <= room_size) {
+        return None;
+    }
+    // A side no longer than a room isn't cut across any more
+    let dir = match (width > room_size, height > room_size) {
+        (true, false) => Direction::East,
+        (false, true) => Direction::South,
+        _             => split(rng, weights)
+    };
+    // Based on the direction we define the line (horizontal or vertical)
+    // and the areas it delineate
+    let (line, areas) = match dir {
+        Direction::South => {
+            let y = position(rng, a.ys, a.ye, division.centering);
+            (Line { fixed: y, low: a.xs, high: a.xe, dir }, (Area { ye: y, ..a }, Area { ys: y + 1, ..a }))
+        }
+        _ => {
+            let x = position(rng, a.xs, a.xe, division.centering);
+            (Line { fixed: x, low: a.ys, high: a.ye, dir }, (Area { xe: x, ..a }, Area { xs: x + 1, ..a }))
+        }
+    };
+    // Divide the area into two areas with passages between them
+    close_line(grid, &line);
+    let length = line.high - line.low + 1;
+    for i in sample_indices(rng, length, division.passages.clamp(1, length)) {
+        match line.dir {
+            Direction::South => grid.open(line.low + i, line.fixed, line.dir),
+            _                => grid.open(line.fixed, line.low + i, line.dir)
+        }
+    }
+    Some(areas)
+}
+
+// A South line is a horizontal wall, so it favors the east-west passages
+fn split<R: Rng>(rng: &mut R, weights: Weights) -> Direction {
+    let horizontal = weights.east + weights.west;
+    let vertical = weights.north + weights.south;
+    if horizontal == vertical {
+        *rng.choose(&[Direction::South, Direction::East]).unwrap()
+    } else if rng.gen_range(0, horizontal + vertical) < horizontal {
+        Direction::South
+    } else {
+        Direction::East
     }
-    result
 }
 
-fn close_line(grid: &mut Grid, l: Line) {
+// The cell a line runs along, from low to high but before the last cell. Centering
+// pulls it towards the middle, 100 percent always splits the area in two equal halves.
+fn position<R: Rng>(rng: &mut R, low: usize, high: usize, centering: usize) -> usize {
+    let pos = rng.gen_range(low, high);
+    let middle = (low + high - 1) as f32 / 2.0;
+    let pull = centering.min(100) as f32 / 100.0;
+    (pos as f32 + (middle - pos as f32) * pull).round() as usize
+}
+
+fn close_line(grid: &mut Grid, l: &Line) {
     // The line can be horizontal or vertical, based on the direction
-    let mut close_passage = match l.dir {
-        Direction::North | Direction::South => {
-            Box::new(|i| grid.close(i, l.fixed, l.dir)) as Box<FnMut(usize) -> ()>
+    for i in l.low ..= l.high {
+        match l.dir {
+            Direction::South => grid.close(i, l.fixed, l.dir),
+            _                => grid.close(l.fixed, i, l.dir)
         }
-        Direction::East | Direction::West   => {
-            Box::new(|i| grid.close(l.fixed, i, l.dir)) as Box<FnMut(usize) -> ()>
-        }
-    };
-    for i in l.low .. l.high {
-        close_passage(i);
     }
 }
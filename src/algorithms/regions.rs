@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+use rand::Rng;
+use grid::{Carve, Crossing, Direction, Grid, PLANAR};
+use super::{blobby_division, growing_tree, hunt_kill_algorithm, kruskals_algorithm, prims_algorithm,
+    recursive_backtracking};
+use super::{Algorithm, Hunt, Prims, Weights};
+use super::kruskals_algorithm::Sets;
+
+// A part of the maze carved with an algorithm of its own
+#[derive(Clone, Debug)]
+pub struct Region {
+    cells: Vec<(usize, usize)>,
+    algorithm: Algorithm
+}
+
+impl Region {
+    pub fn rect(x: usize, y: usize, width: usize, height: usize, algorithm: Algorithm) -> Region {
+        let cells = (x .. x + width).flat_map(|x| (y .. y + height).map(move |y| (x, y))).collect();
+        Region { cells, algorithm }
+    }
+
+    // Any set of cells, which doesn't need to be connected
+    pub fn masked<I: IntoIterator<Item = (usize, usize)>>(cells: I, algorithm: Algorithm) -> Region {
+        Region { cells: cells.into_iter().collect(), algorithm }
+    }
+}
+
+// Carves every region with its algorithm, and the cells outside of all regions with the
+// base algorithm. A cell in several regions belongs to the last one. The generic
+// algorithms (recursive backtracking, hunt and kill, Prim's, growing tree, Kruskal's and
+// blobby division) carve a region in place, whatever its shape. The others carve the rectangle around
+// the region, and only the passages inside the region are kept.
+// The parts are then joined the way Kruskal's algorithm joins cells, so the maze is
+// perfect. Two regions that get joined are joined by the given number of doors, more
+// than one adds loops.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, base: Algorithm, weights: Weights, regions: &[Region],
+    doors: usize) {
+    let (width, height) = (grid.width, grid.height);
+    // The region of every cell, 0 is the base, and the position of the cell in it
+    let mut labels = vec![0; width * height];
+    for (label, region) in regions.iter().enumerate() {
+        for &(x, y) in &region.cells {
+            if x >= width || y >= height {
+                panic!("Region cell ({}, {}) is outside of the maze.", x, y);
+            }
+            labels[x * height + y] = label + 1;
+        }
+    }
+    let mut parts: Vec<Vec<(usize, usize)>> = vec![Vec::new(); regions.len() + 1];
+    let mut slots = vec![0; width * height];
+    for x in 0 .. width {
+        for y in 0 .. height {
+            let label = labels[x * height + y];
+            slots[x * height + y] = parts[label].len();
+            parts[label].push((x, y));
+        }
+    }
+    for (label, cells) in parts.iter().enumerate() {
+        if cells.is_empty() {
+            continue;
+        }
+        let algorithm = if label == 0 { base } else { regions[label - 1].algorithm };
+        let mut part = Part { grid, cells, labels: &labels, slots: &slots, label };
+        match algorithm {
+            Algorithm::RecursiveBacktracking => recursive_backtracking::carve(&mut part, rng, weights),
+            Algorithm::HuntKillAlgorithm     => hunt_kill_algorithm::carve(&mut part, rng, Hunt::Columns, weights),
+            Algorithm::HuntKillAlgorithmWith(order) => hunt_kill_algorithm::carve(&mut part, rng, order, weights),
+            Algorithm::PrimsAlgorithm        => prims_algorithm::carve(&mut part, rng, Prims::Simplified, weights),
+            Algorithm::PrimsAlgorithmWith(prims) => prims_algorithm::carve(&mut part, rng, prims, weights),
+            Algorithm::GrowingTree(cell_selection) => growing_tree::carve(&mut part, rng, cell_selection, weights),
+            Algorithm::KruskalsAlgorithm     => kruskals_algorithm::carve(&mut part, rng),
+            Algorithm::BlobbyDivision(room_size) => blobby_division::carve(&mut part, rng, room_size),
+            _                                => part.carve_around(rng, algorithm, weights)
+        }
+    }
+    join_parts(grid, rng, &labels, doors);
+}
+
+// The cells of a region, as a grid the generic algorithms can carve
+struct Part<'a> {
+    grid: &'a mut Grid,
+    cells: &'a [(usize, usize)],
+    labels: &'a [usize],
+    slots: &'a [usize],
+    label: usize
+}
+
+impl<'a> Part<'a> {
+    fn inside(&self, (x, y): (usize, usize)) -> bool {
+        self.labels[x * self.grid.height + y] == self.label
+    }
+
+    // Carve the rectangle around the region on a grid of its own, and copy the
+    // passages between cells of the region. A crossing is only copied when all of its
+    // neighbors are in the region.
+    fn carve_around<R: Rng>(&mut self, rng: &mut R, algorithm: Algorithm, weights: Weights) {
+        let x0 = self.cells.iter().map(|&(x, _)| x).min().unwrap();
+        let y0 = self.cells.iter().map(|&(_, y)| y).min().unwrap();
+        let x1 = self.cells.iter().map(|&(x, _)| x).max().unwrap();
+        let y1 = self.cells.iter().map(|&(_, y)| y).max().unwrap();
+        let mut around = Grid::new(x1 - x0 + 1, y1 - y0 + 1, true);
+        super::carve(&mut around, rng, algorithm, weights);
+        let crossing = |x: usize, y: usize| around.crossing(x - x0, y - y0);
+        let whole = |part: &Part, x: usize, y: usize| {
+            part.inside((x, y)) && PLANAR.iter().all(|&dir| {
+                around.cell_at(x - x0, y - y0, dir).is_some_and(|(nx, ny)| part.inside((nx + x0, ny + y0)))
+            })
+        };
+        for &(x, y) in self.cells {
+            if let Some(under) = crossing(x, y) {
+                if whole(self, x, y) {
+                    self.grid.add_crossing(x, y, under);
+                }
+                continue;
+            }
+            for &dir in &[Direction::East, Direction::South] {
+                let next = match around.cell_at(x - x0, y - y0, dir) {
+                    Some((nx, ny)) => (nx + x0, ny + y0),
+                    None           => continue
+                };
+                let broken = crossing(next.0, next.1).is_some() && !whole(self, next.0, next.1);
+                if self.inside(next) && !broken && !around.has_wall(x - x0, y - y0, dir) {
+                    self.grid.open(x, y, dir);
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Carve for Part<'a> {
+    type Pos = (usize, usize);
+
+    fn size(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn position(&self, index: usize) -> (usize, usize) {
+        self.cells[index]
+    }
+
+    fn coordinates(&self, (x, y): (usize, usize)) -> (usize, usize, usize) {
+        (x, y, 0)
+    }
+
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        self.slots[x * self.grid.height + y]
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        &PLANAR
+    }
+
+    fn neighbor(&self, (x, y): (usize, usize), dir: Direction) -> Option<(usize, usize)> {
+        self.grid.cell_at(x, y, dir).filter(|&next| self.inside(next))
+    }
+
+    fn is_closed(&self, (x, y): (usize, usize)) -> bool {
+        self.grid.cell(x, y).is_closed()
+    }
+
+    fn open(&mut self, (x, y): (usize, usize), dir: Direction) {
+        self.grid.open(x, y, dir);
+    }
+}
+
+// Join the parts of the maze: the regions, and the pieces a region was cut into
+fn join_parts<R: Rng>(grid: &mut Grid, rng: &mut R, labels: &[usize], doors: usize) {
+    let height = grid.height;
+    let index = |(x, y): (usize, usize)| x * height + y;
+    let mut sets = Sets::new(grid.width * height);
+    let mut walls = Vec::new();
+    for x in 0 .. grid.width {
+        for y in 0 .. height {
+            // The passage over a crossing joins it to the cells on both sides
+            let over = match grid.crossing(x, y) {
+                Some(Crossing::Horizontal) => [Direction::North, Direction::South],
+                Some(Crossing::Vertical)   => [Direction::East, Direction::West],
+                None                       => [Direction::East, Direction::South]
+            };
+            for &dir in &over {
+                let next = if grid.crossing(x, y).is_some() { grid.cell_at(x, y, dir) } else { grid.passage(x, y, dir) };
+                if let Some(next) = next {
+                    sets.reparent(index((x, y)), index(next));
+                }
+            }
+            for &dir in &[Direction::East, Direction::South] {
+                match grid.cell_at(x, y, dir) {
+                    Some(next) if grid.has_wall(x, y, dir) => walls.push(((x, y), dir, next)),
+                    _ => {}
+                }
+            }
+        }
+    }
+    rng.shuffle(&mut walls);
+    // The walls between two regions that weren't opened, for the extra doors
+    let mut spare: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    let mut joined = Vec::new();
+    for &((x, y), dir, next) in &walls {
+        let (a, b) = (labels[index((x, y))], labels[index(next)]);
+        let pair = (a.min(b), a.max(b));
+        if sets.reparent(index((x, y)), index(next)) {
+            grid.open(x, y, dir);
+            if a != b {
+                joined.push(pair);
+            }
+        } else if a != b {
+            spare.entry(pair).or_default().push(((x, y), dir));
+        }
+    }
+    for pair in joined {
+        if let Some(walls) = spare.get_mut(&pair) {
+            for ((x, y), dir) in walls.drain(.. walls.len().min(doors.saturating_sub(1))) {
+                grid.open(x, y, dir);
+            }
+        }
+    }
+}
@@ -11,8 +11,10 @@ use super::Scan;
 // 4. If a passage was not opened, choose (in random) one of the cells in the current set 
 //          and open a passage north. Mark the next cell as the starting point of the set.
 // 5. Continue until all rows have been processed.
-pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R, scan_dir: Scan) -> Grid {
-    let mut grid = Grid::new(width, height, true);
+// When the grid wraps around along the runs without turning upside down, every line
+// starts at a random cell so the runs go around the edge as well.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, scan_dir: Scan) {
+    let (width, height) = (grid.width, grid.height);
     let mut set_start = 0;
     // The algorithm can scan the maze Horizontally or vertically, setup
     // the run variables so they will fit.
@@ -20,21 +22,27 @@ pub fn run<R: Rng>(width: usize, height: usize, rng: &mut R, scan_dir: Scan) ->
         Scan::Horizontal => (width, height, Direction::North, Direction::East),
         Scan::Vertical => (height, width, Direction::West, Direction::South)
     };
+    let (last_x, last_y) = match scan_dir {
+        Scan::Horizontal => (width - 1, 0),
+        Scan::Vertical   => (0, height - 1)
+    };
+    let wraps = grid.cell_at(last_x, last_y, side) == Some((0, 0));
     for j in 0 .. cross {
+        let first = if wraps { rng.gen_range(0, main) } else { 0 };
         for i in 0 .. main {
-            if j == 0 || rng.gen() {
+            // The run always ends at the last cell, or its set would stay cut off
+            if j == 0 || (i != main - 1 && rng.gen()) {
                 if i != main - 1 {
-                    open(scan_dir, &mut grid, i, j, side);
+                    open(scan_dir, grid, (first + i) % main, j, side);
                 }
             } else {
                 let idx = rng.gen_range(set_start, i + 1);
                 set_start = i + 1;                   
-                open(scan_dir, &mut grid, idx, j, up);
+                open(scan_dir, grid, (first + idx) % main, j, up);
             }
         }
         set_start = 0;
     }
-    grid
 }
 
 pub fn open(scan_dir: Scan, grid: &mut Grid, x: usize, y: usize, dir: Direction) {
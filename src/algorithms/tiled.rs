@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use rand::{Rng, SeedableRng, StdRng};
+use grid::{Direction, Grid};
+use super::{Algorithm, Weights};
+use super::kruskals_algorithm::Sets;
+
+// Splits the grid into tiles and carves every tile as a maze of its own, on a pool of
+// threads. A tile is a spanning tree of its cells, so the tiles are then joined the way
+// Kruskal's algorithm joins cells: the walls between tiles are shuffled, and a wall is
+// opened when it separates two tiles that aren't connected yet. The result is perfect
+// as long as the tiles are: the loops of the cellular automaton, and of recursive or
+// blobby division with rooms or extra passages, stay in the maze.
+// Every tile has its own generator seeded from rng, so a seed still gives the same maze
+// whatever the number of threads.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, algorithm: Algorithm, weights: Weights,
+    tile_width: usize, tile_height: usize) {
+    let (columns, rows) = (grid.width.div_ceil(tile_width), grid.height.div_ceil(tile_height));
+    let tiles = columns * rows;
+    let seeds: Vec<usize> = (0 .. tiles).map(|_| rng.gen()).collect();
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(tiles);
+    let (width, height) = (grid.width, grid.height);
+    // The size and top left corner of a tile
+    let bounds = |tile: usize| {
+        let (x, y) = (tile / rows * tile_width, tile % rows * tile_height);
+        (x, y, tile_width.min(width - x), tile_height.min(height - y))
+    };
+    let next_tile = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        // A small queue keeps the finished tiles waiting to be copied in check
+        let (sender, receiver) = mpsc::sync_channel(threads);
+        for _ in 0 .. threads {
+            let sender = sender.clone();
+            let (next_tile, seeds, bounds) = (&next_tile, &seeds, &bounds);
+            scope.spawn(move || loop {
+                let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                if tile >= tiles {
+                    break;
+                }
+                let (x, y, w, h) = bounds(tile);
+                let mut tile_grid = Grid::new(w, h, true);
+                let mut tile_rng = StdRng::from_seed(&[seeds[tile]][..]);
+                super::carve(&mut tile_grid, &mut tile_rng, algorithm, weights);
+                if sender.send((x, y, tile_grid)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for (x, y, tile_grid) in receiver {
+            copy_tile(grid, x, y, &tile_grid);
+        }
+    });
+    join_tiles(grid, rng, tile_width, tile_height);
+}
+
+// Open the passages inside the tile. Its borders stay closed.
+fn copy_tile(grid: &mut Grid, x0: usize, y0: usize, tile: &Grid) {
+    for x in 0 .. tile.width {
+        for y in 0 .. tile.height {
+            if x + 1 < tile.width && !tile.has_wall(x, y, Direction::East) {
+                grid.open(x0 + x, y0 + y, Direction::East);
+            }
+            if y + 1 < tile.height && !tile.has_wall(x, y, Direction::South) {
+                grid.open(x0 + x, y0 + y, Direction::South);
+            }
+        }
+    }
+    if tile.has_crossings() {
+        for x in 0 .. tile.width {
+            for y in 0 .. tile.height {
+                if let Some(under) = tile.crossing(x, y) {
+                    grid.add_crossing(x0 + x, y0 + y, under);
+                }
+            }
+        }
+    }
+}
+
+// The walls between tiles, including the ones around the edges of a wrapping grid
+fn join_tiles<R: Rng>(grid: &mut Grid, rng: &mut R, tile_width: usize, tile_height: usize) {
+    let (columns, rows) = (grid.width.div_ceil(tile_width), grid.height.div_ceil(tile_height));
+    let tile = |(x, y): (usize, usize)| x / tile_width * rows + y / tile_height;
+    // The last column and row of every tile
+    let seams = |length: usize, tile_length: usize| {
+        (1 ..= length.div_ceil(tile_length)).map(move |i| (i * tile_length).min(length) - 1)
+    };
+    let mut edges = Vec::new();
+    for x in seams(grid.width, tile_width) {
+        edges.extend((0 .. grid.height).map(|y| ((x, y), Direction::East)));
+    }
+    for y in seams(grid.height, tile_height) {
+        edges.extend((0 .. grid.width).map(|x| ((x, y), Direction::South)));
+    }
+    // Some of them are on a border, or join a tile to itself
+    edges.retain(|&((x, y), dir)| grid.cell_at(x, y, dir).is_some_and(|next| tile((x, y)) != tile(next)));
+    rng.shuffle(&mut edges);
+    let mut sets = Sets::new(columns * rows);
+    for &((x, y), dir) in &edges {
+        let next = grid.cell_at(x, y, dir).unwrap();
+        if sets.reparent(tile((x, y)), tile(next)) {
+            grid.open(x, y, dir);
+        }
+    }
+}
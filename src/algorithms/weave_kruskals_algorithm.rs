@@ -0,0 +1,68 @@
+use rand::Rng;
+use grid::{Carve, Crossing, Grid};
+use super::kruskals_algorithm::{Sets, collect_edges};
+
+// Based on http://weblog.jamisbuck.org/2011/3/17/weave-mazes-your-take
+// The algorithm:
+// 1. Collect all unique edges in the maze into a set of sets, as in Kruskal's algorithm.
+// 2. Visit the inner cells of the maze in random order, and try to place a crossing at 
+//      each cell until density percent of them have been tried.
+// 3. A crossing can only be placed where the cell and its four neighbors are still closed. 
+//      Open all four walls, pick at random which passage goes under, and join the sets 
+//      of the cells along each passage.
+// 4. Continue with Kruskal's algorithm on the edges that don't touch a crossing.
+pub fn carve<R: Rng>(grid: &mut Grid, rng: &mut R, density: usize) {
+    let (width, height) = (grid.width, grid.height);
+    let mut sets = Sets::new(grid.size());
+    // Pre-seed the crossings. Cells on the border can't have one.
+    let mut candidates = Vec::new();
+    for x in 1 .. width.saturating_sub(1) {
+        for y in 1 .. height.saturating_sub(1) {
+            candidates.push((x, y));
+        }
+    }
+    rng.shuffle(&mut candidates);
+    let tries = candidates.len() * density.min(100) / 100;
+    for &(x, y) in candidates.iter().take(tries) {
+        add_crossing(grid, &mut sets, x, y, rng);
+    }
+    let mut edges = collect_edges(grid);
+    rng.shuffle(&mut edges);
+    // Run over all the edges in the maze
+    for &((x, y), dir) in &edges {
+        // This should never fail
+        let (nx, ny) = grid.cell_at(x, y, dir).unwrap();
+        // The walls around a crossing are already open
+        if grid.crossing(x, y).is_some() || grid.crossing(nx, ny).is_some() {
+            continue;
+        }
+        // If the two adjacent cells don't belong to the same set
+        if sets.reparent(grid.index((x, y)), grid.index((nx, ny))) {
+            // Remove the edge between them
+            grid.open(x, y, dir);
+        }
+    }
+}
+
+fn add_crossing<R: Rng>(grid: &mut Grid, sets: &mut Sets, x: usize, y: usize, rng: &mut R) {
+    let (n, s, e, w) = ((x, y - 1), (x, y + 1), (x + 1, y), (x - 1, y));
+    // Every cell involved must still be closed, this also keeps crossings from
+    // being adjacent to one another
+    for &(cx, cy) in &[(x, y), n, s, e, w] {
+        if !grid.cell(cx, cy).is_closed() {
+            return;
+        }
+    }
+    let under = if rng.gen() { Crossing::Horizontal } else { Crossing::Vertical };
+    grid.add_crossing(x, y, under);
+    // The passage over the crossing joins the crossing cell, the one under it only
+    // joins the cells at its ends
+    let (over, tunnel) = match under {
+        Crossing::Horizontal => ((n, s), (w, e)),
+        Crossing::Vertical   => ((w, e), (n, s))
+    };
+    let cell = grid.index((x, y));
+    sets.reparent(cell, grid.index(over.0));
+    sets.reparent(cell, grid.index(over.1));
+    sets.reparent(grid.index(tunnel.0), grid.index(tunnel.1));
+}
@@ -1,51 +1,69 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Direction {
     North,
     South,
     East,
-    West
+    West,
+    Up,
+    Down
 }
 
 impl Direction {
-    fn opposite(self) -> Direction {
+    pub fn opposite(self) -> Direction {
         match self {
             Direction::North => Direction::South,
             Direction::South => Direction::North,
             Direction::East  => Direction::West,
-            Direction::West  => Direction::East
+            Direction::West  => Direction::East,
+            Direction::Up    => Direction::Down,
+            Direction::Down  => Direction::Up
         }
     }
 }
 
-#[derive(Clone)]
+pub const PLANAR: [Direction; 4] = [Direction::North, Direction::South, Direction::East, Direction::West];
+pub const SPATIAL: [Direction; 6] = [Direction::North, Direction::South, Direction::East, Direction::West,
+    Direction::Up, Direction::Down];
+
+// What lies past the edges of a grid. A cylinder wraps east to west, a torus also
+// wraps north to south, and a Moebius strip wraps east to west upside down.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Boundary {
+    Bounded,
+    Cylinder,
+    Torus,
+    Moebius
+}
+
+// The axis of the passage that tunnels under a crossing cell of a weave maze.
+// The perpendicular passage runs over it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Crossing {
+    Horizontal,
+    Vertical
+}
+
+// The walls around a cell, as read from a grid. true means the wall is closed.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Cell {
     north: bool,
     south: bool,
     east: bool,
-    west: bool
+    west: bool,
+    up: bool,
+    down: bool
 }
 
 impl Cell {
-    fn new(closed: bool) -> Cell {
-        Cell { 
-            north: closed, 
-            south: closed, 
-            east: closed, 
-            west: closed 
-        }
-    } 
-
     pub fn is_closed(&self) -> bool {
-        self.north && self.south && self.east && self.west
-    }
-
-    fn set(&mut self, dir: Direction, closed: bool) {
-        match dir {
-            Direction::North => self.north = closed,
-            Direction::South => self.south = closed,
-            Direction::East => self.east = closed,
-            Direction::West => self.west = closed
-        }
+        self.north && self.south && self.east && self.west && self.up && self.down
     }
 
     pub fn get(&self, dir: Direction) -> bool {
@@ -53,27 +71,246 @@ impl Cell {
             Direction::North => self.north,
             Direction::South => self.south,
             Direction::East => self.east,
-            Direction::West => self.west
+            Direction::West => self.west,
+            Direction::Up => self.up,
+            Direction::Down => self.down
         }
     }    
 }
 
+// A fixed number of bits packed in words. A set bit is a closed wall.
+#[derive(Clone)]
+struct Bits {
+    words: Vec<u64>
+}
+
+impl Bits {
+    fn new(len: usize, value: bool) -> Bits {
+        let word = if value { !0 } else { 0 };
+        Bits { words: vec![word; len.div_ceil(64)] }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> bool {
+        (self.words[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    #[inline]
+    fn set(&mut self, i: usize, value: bool) {
+        let mask = 1 << (i % 64);
+        if value {
+            self.words[i / 64] |= mask;
+        } else {
+            self.words[i / 64] &= !mask;
+        }
+    }
+}
+
+// Where a wall is stored
+#[derive(Clone, Copy)]
+enum Wall {
+    Inner(usize),
+    WestBorder(usize),
+    NorthBorder(usize),
+    Stairs(usize)
+}
+
+// The view of a maze that the generic generators (recursive backtracking, hunt and
+// kill, Prim's, growing tree and Kruskal's) carve into. Cells are addressed by a 
+// position, and are numbered from 0 to size - 1 so they can be scanned or sampled.
+pub trait Carve {
+    type Pos: Copy + Eq + Hash;
+
+    fn size(&self) -> usize;
+
+    fn position(&self, index: usize) -> Self::Pos;
+
+    fn index(&self, pos: Self::Pos) -> usize;
+
+    // The column, row and level of a cell
+    fn coordinates(&self, pos: Self::Pos) -> (usize, usize, usize);
+
+    // The directions a cell can have neighbors in
+    fn directions(&self) -> &'static [Direction];
+
+    fn neighbor(&self, pos: Self::Pos, dir: Direction) -> Option<Self::Pos>;
+
+    fn is_closed(&self, pos: Self::Pos) -> bool;
+
+    fn open(&mut self, pos: Self::Pos, dir: Direction);
+}
+
+#[derive(Clone)]
 pub struct Grid {
     pub width: usize,
     pub height: usize,
-    pub cells: Vec<Vec<Cell>>
+    // The east and south walls of every cell, two bits per cell, column by column.
+    // Every wall between two cells is stored once, as the east or south wall of one
+    // of them. Only the west and north borders aren't, and have bits of their own.
+    walls: Bits,
+    west_border: Bits,
+    north_border: Bits,
+    // The up and down stairs of every cell when the grid is a level of a layered
+    // grid. They are all closed until the first one is opened.
+    stairs: Bits,
+    boundary: Boundary,
+    crossings: HashMap<(usize, usize), Crossing>
+}
+
+// A grid is saved by its walls rather than the way they're stored: the east and south
+// walls of every cell as lists of columns, the west and north borders, the up and down
+// stairs of a level if it has any, and the crossings as (x, y, under) entries since
+// formats like JSON only allow string keys. true means the wall is closed.
+#[cfg(feature = "serde")]
+mod saved {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::de::Error;
+    use super::{Bits, Boundary, Crossing, Grid};
+
+    #[derive(Serialize, Deserialize)]
+    struct SavedGrid {
+        width: usize,
+        height: usize,
+        boundary: Boundary,
+        east: Vec<Vec<bool>>,
+        south: Vec<Vec<bool>>,
+        west: Vec<bool>,
+        north: Vec<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        up: Option<Vec<Vec<bool>>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        down: Option<Vec<Vec<bool>>>,
+        #[serde(default)]
+        crossings: Vec<(usize, usize, Crossing)>
+    }
+
+    impl Serialize for Grid {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let (width, height) = (self.width, self.height);
+            let columns = |bits: &Bits, offset: usize| -> Vec<Vec<bool>> {
+                (0 .. width).map(|x| (0 .. height).map(|y| bits.get(2 * (x * height + y) + offset)).collect())
+                    .collect()
+            };
+            let stairs = !self.stairs.is_empty();
+            let mut crossings: Vec<(usize, usize, Crossing)> =
+                self.crossings.iter().map(|(&(x, y), &under)| (x, y, under)).collect();
+            crossings.sort_by_key(|&(x, y, _)| (x, y));
+            SavedGrid {
+                width,
+                height,
+                boundary: self.boundary,
+                east: columns(&self.walls, 0),
+                south: columns(&self.walls, 1),
+                west: (0 .. height).map(|y| self.west_border.get(y)).collect(),
+                north: (0 .. width).map(|x| self.north_border.get(x)).collect(),
+                up: if stairs { Some(columns(&self.stairs, 0)) } else { None },
+                down: if stairs { Some(columns(&self.stairs, 1)) } else { None },
+                crossings
+            }.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Grid {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Grid, D::Error> {
+            load(SavedGrid::deserialize(deserializer)?).map_err(D::Error::custom)
+        }
+    }
+
+    fn load(saved: SavedGrid) -> Result<Grid, String> {
+        let (width, height) = (saved.width, saved.height);
+        if width == 0 || height == 0 {
+            return Err(String::from("the grid width, height must be greater than 0"));
+        }
+        let mut grid = Grid::with_boundary(width, height, true, saved.boundary);
+        check_columns("east", &saved.east, width, height)?;
+        check_columns("south", &saved.south, width, height)?;
+        check_len("west", saved.west.len(), height)?;
+        check_len("north", saved.north.len(), width)?;
+        set_columns(&mut grid.walls, &saved.east, &saved.south);
+        for (y, &closed) in saved.west.iter().enumerate() {
+            grid.west_border.set(y, closed);
+        }
+        for (x, &closed) in saved.north.iter().enumerate() {
+            grid.north_border.set(x, closed);
+        }
+        match (saved.up, saved.down) {
+            (Some(up), Some(down)) => {
+                check_columns("up", &up, width, height)?;
+                check_columns("down", &down, width, height)?;
+                grid.stairs = Bits::new(2 * width * height, true);
+                set_columns(&mut grid.stairs, &up, &down);
+            }
+            (None, None) => {}
+            _ => return Err(String::from("expected both the up and down stairs or neither"))
+        }
+        for (x, y, under) in saved.crossings {
+            if x >= width || y >= height {
+                return Err(format!("the crossing at ({}, {}) is outside the grid", x, y));
+            }
+            grid.crossings.insert((x, y), under);
+        }
+        Ok(grid)
+    }
+
+    fn check_columns<T>(name: &str, columns: &[Vec<T>], width: usize, height: usize) -> Result<(), String> {
+        check_len(name, columns.len(), width)?;
+        columns.iter().try_for_each(|column| check_len(name, column.len(), height))
+    }
+
+    fn check_len(name: &str, len: usize, expected: usize) -> Result<(), String> {
+        if len == expected {
+            Ok(())
+        } else {
+            Err(format!("expected {} {} entries, found {}", expected, name, len))
+        }
+    }
+
+    // Two bits per cell, column by column
+    fn set_columns(bits: &mut Bits, first: &[Vec<bool>], second: &[Vec<bool>]) {
+        let cells = first.iter().flatten().zip(second.iter().flatten());
+        for (i, (&a, &b)) in cells.enumerate() {
+            bits.set(2 * i, a);
+            bits.set(2 * i + 1, b);
+        }
+    }
 }
 
 impl Grid {
     pub fn new(width: usize, height: usize, closed: bool) -> Grid {
-        let cell = Cell::new(closed);
+        Grid::with_boundary(width, height, closed, Boundary::Bounded)
+    }
+
+    pub fn with_boundary(width: usize, height: usize, closed: bool, boundary: Boundary) -> Grid {
         Grid {
-            width: width,
-            height: height,
-            cells: vec![vec![cell; height]; width]
+            width,
+            height,
+            walls: Bits::new(2 * width * height, closed),
+            west_border: Bits::new(height, closed),
+            north_border: Bits::new(width, closed),
+            stairs: Bits::new(0, true),
+            boundary,
+            crossings: HashMap::new()
+        }
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        self.boundary
+    }
+
+    // Whether the side of the grid in direction dir has a border, or wraps around
+    pub fn has_border(&self, dir: Direction) -> bool {
+        match dir {
+            Direction::North | Direction::South => self.boundary != Boundary::Torus,
+            Direction::East  | Direction::West  => self.boundary == Boundary::Bounded,
+            Direction::Up    | Direction::Down  => false
         }
     }
 
+    #[inline]
     pub fn open(&mut self, x: usize, y: usize, dir: Direction) {
         self.set(x, y, dir, false);
     }
@@ -82,20 +319,153 @@ impl Grid {
         self.set(x, y, dir, true);
     }
 
+    // Whether the wall of (x, y) in direction dir is closed
+    #[inline]
+    pub fn has_wall(&self, x: usize, y: usize, dir: Direction) -> bool {
+        match self.wall(x, y, dir) {
+            Wall::Inner(i)       => self.walls.get(i),
+            Wall::WestBorder(i)  => self.west_border.get(i),
+            Wall::NorthBorder(i) => self.north_border.get(i),
+            Wall::Stairs(i)      => self.stairs.is_empty() || self.stairs.get(i)
+        }
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> Cell {
+        Cell {
+            north: self.has_wall(x, y, Direction::North),
+            south: self.has_wall(x, y, Direction::South),
+            east: self.has_wall(x, y, Direction::East),
+            west: self.has_wall(x, y, Direction::West),
+            up: self.has_wall(x, y, Direction::Up),
+            down: self.has_wall(x, y, Direction::Down)
+        }
+    }
+
+    #[inline]
     fn set(&mut self, x: usize, y: usize, dir: Direction, closed: bool) {
-        self.cells[x][y].set(dir, closed);
-        if let Some((nx, ny)) = self.cell_at(x, y, dir) {
-            self.cells[nx][ny].set(dir.opposite(), closed);
+        match self.wall(x, y, dir) {
+            Wall::Inner(i)       => self.walls.set(i, closed),
+            Wall::WestBorder(i)  => self.west_border.set(i, closed),
+            Wall::NorthBorder(i) => self.north_border.set(i, closed),
+            Wall::Stairs(_) if closed && self.stairs.is_empty() => {}
+            Wall::Stairs(i)      => {
+                if self.stairs.is_empty() {
+                    self.stairs = Bits::new(2 * self.width * self.height, true);
+                }
+                self.stairs.set(i, closed);
+            }
+        }
+    }
+
+    // A west or north wall is the east or south wall of the cell on its other side
+    #[inline]
+    fn wall(&self, x: usize, y: usize, dir: Direction) -> Wall {
+        let bit = |(x, y): (usize, usize)| 2 * (x * self.height + y);
+        match dir {
+            Direction::East  => Wall::Inner(bit((x, y))),
+            Direction::South => Wall::Inner(bit((x, y)) + 1),
+            Direction::West  if x > 0 => Wall::Inner(bit((x - 1, y))),
+            Direction::North if y > 0 => Wall::Inner(bit((x, y - 1)) + 1),
+            Direction::West  => match self.cell_at(x, y, dir) {
+                Some(pos) => Wall::Inner(bit(pos)),
+                None      => Wall::WestBorder(y)
+            },
+            Direction::North => match self.cell_at(x, y, dir) {
+                Some(pos) => Wall::Inner(bit(pos) + 1),
+                None      => Wall::NorthBorder(x)
+            },
+            Direction::Up    => Wall::Stairs(bit((x, y))),
+            Direction::Down  => Wall::Stairs(bit((x, y)) + 1)
+        }
+    }
+
+    // Turn (x, y) into a crossing: all four walls are opened, and the passage along
+    // the under axis tunnels beneath the one running over it.
+    pub fn add_crossing(&mut self, x: usize, y: usize, under: Crossing) {
+        for &dir in &PLANAR {
+            self.open(x, y, dir);
+        }
+        self.crossings.insert((x, y), under);
+    }
+
+    pub fn crossing(&self, x: usize, y: usize) -> Option<Crossing> {
+        self.crossings.get(&(x, y)).cloned()
+    }
+
+    pub fn has_crossings(&self) -> bool {
+        !self.crossings.is_empty()
+    }
+
+    // Follow the passage leaving (x, y) in direction dir, if the wall is open.
+    // A passage can't turn inside a crossing cell (whether it goes over or under), 
+    // so crossings are passed straight through to the next regular cell.
+    pub fn passage(&self, x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
+        let (mut x, mut y) = (x, y);
+        loop {
+            if self.has_wall(x, y, dir) {
+                return None;
+            }
+            let (nx, ny) = self.cell_at(x, y, dir)?;
+            match self.crossings.get(&(nx, ny)) {
+                Some(_) => { x = nx; y = ny; }
+                None    => return Some((nx, ny))
+            }
         }
     }
 
     pub fn cell_at(&self, x: usize, y: usize, dir: Direction) -> Option<(usize, usize)> {
+        let (last_x, last_y) = (self.width - 1, self.height - 1);
+        let flipped = self.boundary == Boundary::Moebius;
         match dir {
             Direction::North if y > 0                 => Some((x, y - 1)),
-            Direction::South if y < self.height - 1   => Some((x, y + 1)),
-            Direction::East  if x < self.width - 1    => Some((x + 1, y)),
+            Direction::South if y < last_y            => Some((x, y + 1)),
+            Direction::East  if x < last_x            => Some((x + 1, y)),
             Direction::West  if x > 0                 => Some((x - 1, y)),
+            // Past the edges, when they wrap around
+            Direction::North if !self.has_border(dir) => Some((x, last_y)),
+            Direction::South if !self.has_border(dir) => Some((x, 0)),
+            Direction::East  if flipped               => Some((0, last_y - y)),
+            Direction::West  if flipped               => Some((last_x, last_y - y)),
+            Direction::East  if !self.has_border(dir) => Some((0, y)),
+            Direction::West  if !self.has_border(dir) => Some((last_x, y)),
             _                                         => None           
         }
     }   
 }
+
+impl Carve for Grid {
+    type Pos = (usize, usize);
+
+    fn size(&self) -> usize {
+        self.width * self.height
+    }
+
+    // Column by column, the same order as the cells are stored in
+    fn position(&self, index: usize) -> (usize, usize) {
+        (index / self.height, index % self.height)
+    }
+
+    fn coordinates(&self, (x, y): (usize, usize)) -> (usize, usize, usize) {
+        (x, y, 0)
+    }
+
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        x * self.height + y
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        &PLANAR
+    }
+
+    fn neighbor(&self, (x, y): (usize, usize), dir: Direction) -> Option<(usize, usize)> {
+        self.cell_at(x, y, dir)
+    }
+
+    fn is_closed(&self, (x, y): (usize, usize)) -> bool {
+        self.cell(x, y).is_closed()
+    }
+
+    fn open(&mut self, (x, y): (usize, usize), dir: Direction) {
+        Grid::open(self, x, y, dir);
+    }
+}
@@ -0,0 +1,183 @@
+use image::{self, DynamicImage, GrayImage};
+use std::io::{self, Error, ErrorKind};
+use grid::{Boundary, Crossing, Direction, Grid};
+use render::{self, BASE_CELL_LENGTH, BASE_STROKE_WIDTH, MARGIN_LENGTH};
+
+const THRESHOLD: u8 = 128;
+
+// Reads back the walls of a maze drawn by MazeRender. The image must have been
+// rendered with the same geometry, which is the crate's fixed geometry at scale 1
+// unless set otherwise. Only mazes with a single level can be imported. Images of
+// weave mazes are recognized by their inset cells, and their crossings are restored.
+pub struct MazeImport {
+    stroke_width: u32,
+    cell_length: u32,
+    margin_length: u32,
+    boundary: Boundary
+}
+
+impl Default for MazeImport {
+    fn default() -> MazeImport {
+        MazeImport {
+            stroke_width: BASE_STROKE_WIDTH,
+            cell_length: BASE_CELL_LENGTH,
+            margin_length: MARGIN_LENGTH,
+            boundary: Boundary::Bounded
+        }
+    }
+}
+
+impl MazeImport {
+    pub fn new() -> MazeImport {
+        MazeImport::default()
+    }
+
+    // The scale the image was rendered at, rounded the same way MazeRender does
+    pub fn scale(&mut self, scale: f32) -> &mut MazeImport {
+        self.stroke_width = render::scaled(BASE_STROKE_WIDTH, scale, 1);
+        self.cell_length = render::scaled(BASE_CELL_LENGTH, scale, 1);
+        self.margin_length = render::scaled(MARGIN_LENGTH, scale, 0);
+        self
+    }
+
+    // An explicit geometry in pixels, for images that weren't drawn at a plain scale
+    pub fn geometry(&mut self, cell_length: u32, stroke_width: u32, margin_length: u32) -> &mut MazeImport {
+        if cell_length == 0 || stroke_width == 0 {
+            panic!("The cell length and stroke width must be greater than 0");
+        }
+        self.cell_length = cell_length;
+        self.stroke_width = stroke_width;
+        self.margin_length = margin_length;
+        self
+    }
+
+    // The image doesn't tell how its borders wrap, so openings in them are
+    // read as entry points unless another boundary is given
+    pub fn boundary(&mut self, boundary: Boundary) -> &mut MazeImport {
+        self.boundary = boundary;
+        self
+    }
+
+    pub fn load_from_file(&self, path: &str) -> io::Result<Grid> {
+        let img = image::open(path).map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?;
+        self.import(&img)
+    }
+
+    pub fn import(&self, img: &DynamicImage) -> io::Result<Grid> {
+        let img = img.to_luma();
+        let cell_len = self.cell_length + self.stroke_width;
+        let (width, height) = match (self.cells(img.width()), self.cells(img.height())) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Err(Error::new(ErrorKind::InvalidData, "The image size doesn't match the maze geometry."))
+        };
+        let pixels = Pixels { img: &img, margin: self.margin_length, cell_len };
+        let mut grid = Grid::with_boundary(width, height, true, self.boundary);
+        // In the regular layout the corners between cells are mostly black, while
+        // the corners of inset cells are always blank
+        let inset = (0 .. width).all(|x| (0 .. height).all(|y| !pixels.black(x, y, 0, 0)));
+        if inset {
+            self.read_inset(&pixels, &mut grid);
+        } else {
+            self.read_strokes(&pixels, &mut grid);
+        }
+        Ok(grid)
+    }
+
+    // The number of cells along a side of the image
+    fn cells(&self, length: u32) -> Option<usize> {
+        let cell_len = self.cell_length + self.stroke_width;
+        let inside = length.checked_sub(2 * self.margin_length + self.stroke_width)?;
+        if inside == 0 || inside % cell_len != 0 {
+            return None;
+        }
+        Some((inside / cell_len) as usize)
+    }
+
+    // Every wall is sampled at the middle of its stroke
+    fn read_strokes(&self, pixels: &Pixels, grid: &mut Grid) {
+        let middle = self.stroke_width + self.cell_length / 2;
+        let stroke = self.stroke_width / 2;
+        for x in 0 .. grid.width {
+            for y in 0 .. grid.height {
+                if !pixels.black(x, y, stroke, middle) {
+                    grid.open(x, y, Direction::West);
+                }
+                if !pixels.black(x, y, middle, stroke) {
+                    grid.open(x, y, Direction::North);
+                }
+            }
+        }
+        // The east and south borders are drawn after the last cells
+        for y in 0 .. grid.height {
+            if !pixels.black(grid.width, y, stroke, middle) {
+                grid.open(grid.width - 1, y, Direction::East);
+            }
+        }
+        for x in 0 .. grid.width {
+            if !pixels.black(x, grid.height, middle, stroke) {
+                grid.open(x, grid.height - 1, Direction::South);
+            }
+        }
+    }
+
+    // Every cell draws its own walls, so a wall is shared by the drawings of the two
+    // cells next to it. A crossing looks like a corridor along its passage over, and
+    // only the drawings of its neighbors show that its other sides are open too.
+    fn read_inset(&self, pixels: &Pixels, grid: &mut Grid) {
+        let cell_len = self.cell_length + self.stroke_width;
+        let low = render::inset(self.cell_length, self.stroke_width);
+        let (before, after, middle) = (low - 1, cell_len - low, cell_len / 2);
+        let (width, height) = (grid.width, grid.height);
+        let drawn_closed = |x: usize, y: usize, dir: Direction| match dir {
+            Direction::North => pixels.black(x, y, middle, before),
+            Direction::South => pixels.black(x, y, middle, after),
+            Direction::West  => pixels.black(x, y, before, middle),
+            _                => pixels.black(x, y, after, middle)
+        };
+        let mut crossings = Vec::new();
+        for x in 0 .. width {
+            for y in 0 .. height {
+                let mut drawn = Vec::new();
+                let mut closed_sides = 0;
+                for &dir in &[Direction::North, Direction::South, Direction::East, Direction::West] {
+                    let closed = drawn_closed(x, y, dir);
+                    let neighbor_open = match grid.cell_at(x, y, dir) {
+                        Some((nx, ny)) => !drawn_closed(nx, ny, dir.opposite()),
+                        None           => false
+                    };
+                    if !closed {
+                        grid.open(x, y, dir);
+                    } else if !neighbor_open {
+                        closed_sides += 1;
+                    }
+                    drawn.push(closed);
+                }
+                // A crossing is open on all four sides, but is drawn closed on two of them
+                match (closed_sides, &drawn[..]) {
+                    (0, &[false, false, true, true]) => crossings.push((x, y, Crossing::Horizontal)),
+                    (0, &[true, true, false, false]) => crossings.push((x, y, Crossing::Vertical)),
+                    _ => {}
+                }
+            }
+        }
+        for (x, y, under) in crossings {
+            grid.add_crossing(x, y, under);
+        }
+    }
+}
+
+// The pixels of the image relative to its margins
+struct Pixels<'a> {
+    img: &'a GrayImage,
+    margin: u32,
+    cell_len: u32
+}
+
+impl<'a> Pixels<'a> {
+    // Whether the pixel at (xc, yc) inside the square of cell (x, y) is black
+    fn black(&self, x: usize, y: usize, xc: u32, yc: u32) -> bool {
+        let px = self.margin + x as u32 * self.cell_len + xc;
+        let py = self.margin + y as u32 * self.cell_len + yc;
+        self.img.get_pixel(px, py)[0] < THRESHOLD
+    }
+}
@@ -0,0 +1,108 @@
+use grid::{Boundary, Carve, Direction, Grid, SPATIAL};
+
+// A stack of equally sized levels. Besides the passages inside each level, a cell
+// can have stairs leading to the cell at the same position on the level above (Up)
+// or below (Down) it.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LayeredGrid {
+    pub width: usize,
+    pub height: usize,
+    pub levels: Vec<Grid>
+}
+
+impl LayeredGrid {
+    pub fn new(width: usize, height: usize, depth: usize, closed: bool) -> LayeredGrid {
+        LayeredGrid::with_boundary(width, height, depth, closed, Boundary::Bounded)
+    }
+
+    // Every level has the same boundary
+    pub fn with_boundary(width: usize, height: usize, depth: usize, closed: bool, boundary: Boundary) 
+        -> LayeredGrid {
+        LayeredGrid {
+            width,
+            height,
+            levels: (0 .. depth).map(|_| Grid::with_boundary(width, height, closed, boundary)).collect()
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn open(&mut self, x: usize, y: usize, z: usize, dir: Direction) {
+        self.set(x, y, z, dir, false);
+    }
+
+    pub fn close(&mut self, x: usize, y: usize, z: usize, dir: Direction) {
+        self.set(x, y, z, dir, true);
+    }
+
+    fn set(&mut self, x: usize, y: usize, z: usize, dir: Direction, closed: bool) {
+        // A level knows nothing of the levels around it, so stairs are set on both ends
+        let stairs = match dir {
+            Direction::Up | Direction::Down => self.cell_at(x, y, z, dir),
+            _                               => None
+        };
+        set_level(&mut self.levels[z], x, y, dir, closed);
+        if let Some((_, _, nz)) = stairs {
+            set_level(&mut self.levels[nz], x, y, dir.opposite(), closed);
+        }
+    }
+
+    pub fn cell_at(&self, x: usize, y: usize, z: usize, dir: Direction) -> Option<(usize, usize, usize)> {
+        match dir {
+            Direction::Up   if z < self.depth() - 1 => Some((x, y, z + 1)),
+            Direction::Down if z > 0                => Some((x, y, z - 1)),
+            Direction::Up | Direction::Down         => None,
+            _ => self.levels[z].cell_at(x, y, dir).map(|(nx, ny)| (nx, ny, z))
+        }
+    }
+}
+
+fn set_level(grid: &mut Grid, x: usize, y: usize, dir: Direction, closed: bool) {
+    if closed {
+        grid.close(x, y, dir);
+    } else {
+        grid.open(x, y, dir);
+    }
+}
+
+impl Carve for LayeredGrid {
+    type Pos = (usize, usize, usize);
+
+    fn size(&self) -> usize {
+        self.width * self.height * self.depth()
+    }
+
+    // Level by level, each one column by column
+    fn position(&self, index: usize) -> (usize, usize, usize) {
+        let area = self.width * self.height;
+        let (z, i) = (index / area, index % area);
+        (i / self.height, i % self.height, z)
+    }
+
+    fn coordinates(&self, pos: (usize, usize, usize)) -> (usize, usize, usize) {
+        pos
+    }
+
+    fn index(&self, (x, y, z): (usize, usize, usize)) -> usize {
+        z * self.width * self.height + x * self.height + y
+    }
+
+    fn directions(&self) -> &'static [Direction] {
+        &SPATIAL
+    }
+
+    fn neighbor(&self, (x, y, z): (usize, usize, usize), dir: Direction) -> Option<(usize, usize, usize)> {
+        self.cell_at(x, y, z, dir)
+    }
+
+    fn is_closed(&self, (x, y, z): (usize, usize, usize)) -> bool {
+        self.levels[z].cell(x, y).is_closed()
+    }
+
+    fn open(&mut self, (x, y, z): (usize, usize, usize), dir: Direction) {
+        LayeredGrid::open(self, x, y, z, dir);
+    }
+}
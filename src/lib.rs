@@ -1,29 +1,58 @@
 extern crate rand;
 extern crate image;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 mod grid;
+mod layered;
 mod algorithms;
 mod render;
+mod text;
+mod import;
+pub mod solver;
 
-use algorithms::binary_tree;
-use algorithms::ellers_algorithm;
 use algorithms::recursive_backtracking;
 use algorithms::hunt_kill_algorithm;
 use algorithms::prims_algorithm;
 use algorithms::growing_tree;
-use algorithms::sidewinder_algorithm;
 use algorithms::kruskals_algorithm;
-use algorithms::recursive_division;
-use algorithms::choose_entry_points;
-use grid::Grid;
-use render::MazeRender;
+use algorithms::blobby_division;
+use algorithms::tiled;
+use algorithms::regions;
+use algorithms::loops;
+use algorithms::{choose_entry_points, choose_layered_entry_points};
+use rand::{Rng, SeedableRng, StdRng};
 
-pub use algorithms::{Algorithm, CellSelection, Bias, Scan};
+pub use algorithms::{Algorithm, Automaton, CellSelection, Bias, Division, Generator, Hunt, Prims, Rule, Scan,
+    Weights};
+pub use algorithms::ellers_algorithm::{EllersLines, Line};
+pub use algorithms::growing_tree::{Either, Farthest, Middle, Mix, Newest, Oldest, Random, Selection};
+pub use algorithms::loops::Loops;
+pub use algorithms::origin_shift::OriginShift;
+pub use algorithms::regions::Region;
+pub use grid::{Boundary, Cell, Crossing, Direction, Grid};
+pub use layered::LayeredGrid;
+pub use render::MazeRender;
+pub use text::MazeText;
+pub use import::MazeImport;
 
 pub struct Maze {
     width: usize,
     height: usize,
-    algorithm: Algorithm 
+    levels: usize,
+    boundary: Boundary,
+    algorithm: Algorithm,
+    generator: Option<Box<dyn Generator>>,
+    selection: Option<Box<dyn Selection>>,
+    tiles: Option<(usize, usize)>,
+    regions: Vec<Region>,
+    doors: usize,
+    weights: Weights,
+    loops: Option<Loops>,
+    seed: Option<u64>
 }
 
 impl Maze {
@@ -34,40 +63,217 @@ impl Maze {
         Maze {
             width: width,
             height: height,
-            algorithm: Algorithm::RecursiveBacktracking
+            levels: 1,
+            boundary: Boundary::Bounded,
+            algorithm: Algorithm::RecursiveBacktracking,
+            generator: None,
+            selection: None,
+            tiles: None,
+            regions: Vec::new(),
+            doors: 1,
+            weights: Weights::uniform(),
+            loops: None,
+            seed: None
         }
     }
 
+    // Stack several levels connected by stairs. Only the recursive backtracking, 
+    // hunt and kill, Prim's, growing tree, Kruskal's and blobby division algorithms
+    // can carve them.
+    pub fn levels(&mut self, levels: usize) -> &mut Maze {
+        if levels == 0 {
+            panic!("Maze levels must be greater than 0.");
+        }
+        self.levels = levels;
+        self
+    }
+
+    // Binary tree, recursive division and the cellular automaton never carve passages
+    // around the edges of a wrapping boundary. Eller's and sidewinder only carve around
+    // the edges their lines run into, east and west when they scan horizontally and north
+    // and south when they scan vertically, and not on a Moebius strip. The walls they
+    // don't carve are left closed.
+    pub fn boundary(&mut self, boundary: Boundary) -> &mut Maze {
+        check_loops(boundary, self.loops);
+        self.boundary = boundary;
+        self
+    }
+
     pub fn algorithm(&mut self, algorithm: Algorithm) -> &mut Maze {
         self.algorithm = algorithm;
+        self.generator = None;
+        self.selection = None;
+        self
+    }
+
+    // Carve the maze with a generator of your own instead of one of the algorithms. It
+    // can't carve several levels, tiles or regions.
+    pub fn generator<G: Generator + 'static>(&mut self, generator: G) -> &mut Maze {
+        self.generator = Some(Box::new(generator));
+        self.selection = None;
+        self
+    }
+
+    // Carve the maze with growing tree and a selection of your own instead of one of the
+    // algorithms. It can carve several levels, but not tiles or regions.
+    pub fn selection<S: Selection + 'static>(&mut self, selection: S) -> &mut Maze {
+        self.selection = Some(Box::new(selection));
+        self.generator = None;
         self
     }
 
-    fn generate_grid(&mut self) -> Grid {
-        let mut rng = rand::thread_rng();
-        let width = self.width;
-        let height = self.height;
-        let mut grid = match self.algorithm {
-            Algorithm::BinaryTree(bias)            => binary_tree::run(width, height, &mut rng, bias),
-            Algorithm::EllersAlgorithm(scan)       => ellers_algorithm::run(width, height, &mut rng, scan),
-            Algorithm::RecursiveBacktracking       => recursive_backtracking::run(width, height, &mut rng),
-            Algorithm::HuntKillAlgorithm           => hunt_kill_algorithm::run(width, height, &mut rng),
-            Algorithm::PrimsAlgorithm              => prims_algorithm::run(width, height, &mut rng),
-            Algorithm::GrowingTree(cell_selection) => growing_tree::run(width, height, &mut rng, cell_selection),
-            Algorithm::SidewinderAlgorithm(scan)   => sidewinder_algorithm::run(width, height, &mut rng, scan),
-            Algorithm::KruskalsAlgorithm           => kruskals_algorithm::run(width, height, &mut rng),
-            Algorithm::RecursiveDivision           => recursive_division::run(width, height, &mut rng)
+    // Generate the maze in tiles of the given size, each on its own thread, then join
+    // them into a single maze. Meant for huge mazes, the seams between the tiles are
+    // long walls with a few doors. A maze with several levels can't be tiled.
+    pub fn tiles(&mut self, width: usize, height: usize) -> &mut Maze {
+        if width == 0 || height == 0 {
+            panic!("Tile width, height must be greater than 0.");
+        }
+        self.tiles = Some((width, height));
+        self
+    }
+
+    // Carve a part of the maze with an algorithm of its own. The rest of the maze is
+    // carved with the maze's algorithm, and the parts are joined into a perfect maze.
+    // Regions take precedence over tiles, and a maze with several levels has none.
+    pub fn region(&mut self, region: Region) -> &mut Maze {
+        self.regions.push(region);
+        self
+    }
+
+    // The number of doors between two joined regions. More than one adds loops.
+    pub fn doors(&mut self, doors: usize) -> &mut Maze {
+        if doors == 0 {
+            panic!("Maze doors must be greater than 0.");
+        }
+        self.doors = doors;
+        self
+    }
+
+    // Make the recursive backtracking, hunt and kill, Prim's, growing tree and recursive
+    // division algorithms favor some directions over others
+    pub fn weights(&mut self, weights: Weights) -> &mut Maze {
+        self.weights = weights;
+        self
+    }
+
+    // Knock extra loops through the walls once the maze is carved. A maze with several
+    // levels has none. A torus has no entry and exit, so it can't be given a number of
+    // solutions.
+    pub fn loops(&mut self, loops: Loops) -> &mut Maze {
+        check_loops(self.boundary, Some(loops));
+        self.loops = Some(loops);
+        self
+    }
+
+    // The same seed and settings always generate the same maze
+    pub fn seed(&mut self, seed: u64) -> &mut Maze {
+        self.seed = Some(seed);
+        self
+    }
+
+    fn rng(&self) -> StdRng {
+        let seed = match self.seed {
+            Some(seed) => seed as usize,
+            None       => rand::thread_rng().gen()
         };
+        StdRng::from_seed(&[seed][..])
+    }
+
+    pub fn generate(&mut self) -> Grid {
+        if self.levels > 1 {
+            panic!("A maze with several levels must be generated with generate_layers.");
+        }
+        let mut rng = self.rng();
+        let mut grid = Grid::with_boundary(self.width, self.height, true, self.boundary);
+        match self.tiles {
+            _ if self.generator.is_some() => {
+                if self.tiles.is_some() || !self.regions.is_empty() {
+                    panic!("A generator can't generate a maze in tiles or regions.");
+                }
+                self.generator.as_mut().unwrap().carve(&mut grid, &mut rng);
+            }
+            _ if self.selection.is_some() => {
+                if self.tiles.is_some() || !self.regions.is_empty() {
+                    panic!("A selection can't generate a maze in tiles or regions.");
+                }
+                growing_tree::run(&mut grid, &mut rng, &mut **self.selection.as_mut().unwrap(), self.weights);
+            }
+            _ if !self.regions.is_empty() => {
+                regions::carve(&mut grid, &mut rng, self.algorithm, self.weights, &self.regions, self.doors);
+            }
+            Some((tile_width, tile_height)) => {
+                tiled::carve(&mut grid, &mut rng, self.algorithm, self.weights, tile_width, tile_height);
+            }
+            None => algorithms::carve(&mut grid, &mut rng, self.algorithm, self.weights)
+        }
         choose_entry_points(&mut grid, &mut rng);
+        if let Some(loops) = self.loops {
+            loops::insert(&mut grid, &mut rng, loops);
+        }
+        grid
+    }
+
+    pub fn generate_layers(&mut self) -> LayeredGrid {
+        let mut rng = self.rng();
+        if self.generator.is_some() {
+            panic!("A generator can't generate a maze with several levels.");
+        }
+        if self.tiles.is_some() {
+            panic!("A maze with several levels can't be generated in tiles.");
+        }
+        let mut grid = LayeredGrid::with_boundary(self.width, self.height, self.levels, true, self.boundary);
+        match self.algorithm {
+            _ if self.selection.is_some() => {
+                growing_tree::run(&mut grid, &mut rng, &mut **self.selection.as_mut().unwrap(), self.weights)
+            }
+            Algorithm::RecursiveBacktracking       => recursive_backtracking::carve(&mut grid, &mut rng, self.weights),
+            Algorithm::HuntKillAlgorithm           => {
+                hunt_kill_algorithm::carve(&mut grid, &mut rng, Hunt::Columns, self.weights)
+            }
+            Algorithm::HuntKillAlgorithmWith(order) => {
+                hunt_kill_algorithm::carve(&mut grid, &mut rng, order, self.weights)
+            }
+            Algorithm::PrimsAlgorithm              => {
+                prims_algorithm::carve(&mut grid, &mut rng, Prims::Simplified, self.weights)
+            }
+            Algorithm::PrimsAlgorithmWith(prims)   => prims_algorithm::carve(&mut grid, &mut rng, prims, self.weights),
+            Algorithm::GrowingTree(cell_selection) => {
+                growing_tree::carve(&mut grid, &mut rng, cell_selection, self.weights)
+            }
+            Algorithm::KruskalsAlgorithm           => kruskals_algorithm::carve(&mut grid, &mut rng),
+            Algorithm::BlobbyDivision(room_size)   => blobby_division::carve(&mut grid, &mut rng, room_size),
+            algorithm => panic!("{:?} can't generate a maze with several levels.", algorithm)
+        }
+        choose_layered_entry_points(&mut grid, &mut rng);
         grid
     }
 
     pub fn render(&mut self, scale: f32) -> MazeRender {
-        let mut grid = self.generate_grid();
-        let mut maze_render = MazeRender::new(&mut grid);
+        let mut maze_render = if self.levels > 1 {
+            MazeRender::from_layers(&self.generate_layers())
+        } else {
+            MazeRender::new(&self.generate())
+        };
         maze_render.scale(scale).render();
         maze_render
     }
+
+    // Only a maze with a single level can be saved as text
+    pub fn text(&mut self) -> MazeText {
+        let mut maze_text = MazeText::new(&self.generate());
+        // A generator or a selection of your own can't be read back
+        let own = self.generator.is_some() || self.selection.is_some();
+        maze_text.algorithm = if own { None } else { Some(self.algorithm) };
+        maze_text.seed = self.seed;
+        maze_text
+    }
+}
+
+fn check_loops(boundary: Boundary, loops: Option<Loops>) {
+    if let (Boundary::Torus, Some(Loops::Solutions(_))) = (boundary, loops) {
+        panic!("A torus has no entry and exit to count the solutions between.");
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,228 @@
+extern crate maze;
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use maze::{Algorithm, Boundary, Loops, Maze, Weights};
+
+const USAGE: &str = "\
+Usage: maze <algorithm> [options]
+
+Algorithms:
+  binary-tree [--bias ne|nw|se|sw]
+  ellers [--scan horizontal|vertical]
+  recursive-backtracking
+  hunt-kill [--hunt columns|rows|random|serpentine]
+  prims [--cost none|cells|edges]
+  growing-tree [--selection newest|oldest|random|middle|newest-oldest:N|newest-random:N|
+                oldest-random:N|farthest-random:N|mix:N,O,R]
+  sidewinder [--scan horizontal|vertical]
+  kruskals
+  recursive-division [--rooms N] [--centering N] [--passages N]
+  blobby-division [--cells N]
+  cellular-automaton [--rule maze|mazectric] [--steps N] [--density N]
+  origin-shift [--steps N]
+  weave-kruskals [--density N]
+
+Options:
+  --width N        The maze width in cells (default 20)
+  --height N       The maze height in cells (default 20)
+  --levels N       The number of levels connected by stairs (default 1)
+  --boundary B     bounded, cylinder, torus or moebius (default bounded)
+  --seed N         Generate the same maze every time
+  --tiles WxH      Generate the maze in tiles of WxH cells on several threads
+  --loops N        Knock N loops through the walls once the maze is carved
+  --solutions N    Knock loops through the walls until there are N ways through
+  --weights W      How often to carve north,south,east,west, e.g. 1,1,3,3. Used by
+                   recursive-backtracking, hunt-kill, prims, growing-tree and
+                   recursive-division.
+  --scale F        The scale of the image (default 1)
+  --format F       png or text. Defaults to the output's extension, or text.
+  --output PATH    Where to save the maze. Text is printed to stdout without it.
+  --help           Show this message
+
+The algorithm options are its settings in the saved text, e.g. --bias ne is bias=ne.
+";
+
+enum Format {
+    Png,
+    Text
+}
+
+struct Options {
+    algorithm: Algorithm,
+    width: usize,
+    height: usize,
+    levels: usize,
+    boundary: Boundary,
+    seed: Option<u64>,
+    tiles: Option<(usize, usize)>,
+    weights: Weights,
+    loops: Option<Loops>,
+    scale: f32,
+    format: Option<Format>,
+    output: Option<String>
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", USAGE);
+        return;
+    }
+    let options = parse_args(&args).unwrap_or_else(|msg| {
+        eprintln!("maze: {}\n\n{}", msg, USAGE);
+        process::exit(2);
+    });
+    if let Err(msg) = run(options) {
+        eprintln!("maze: {}", msg);
+        process::exit(1);
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    // Maze panics on the algorithms that can't carve levels
+    if options.levels > 1 {
+        match options.algorithm {
+            Algorithm::RecursiveBacktracking | Algorithm::HuntKillAlgorithm | Algorithm::HuntKillAlgorithmWith(_) |
+                Algorithm::PrimsAlgorithm | Algorithm::PrimsAlgorithmWith(_) | Algorithm::GrowingTree(_) |
+                Algorithm::KruskalsAlgorithm | Algorithm::BlobbyDivision(_) => {}
+            algorithm => return Err(format!("{:?} can't generate a maze with several levels", algorithm))
+        }
+    }
+    let mut maze = Maze::new(options.width, options.height);
+    maze.algorithm(options.algorithm)
+        .levels(options.levels)
+        .boundary(options.boundary)
+        .weights(options.weights);
+    if let Some(loops) = options.loops {
+        if options.levels > 1 {
+            return Err(String::from("a maze with several levels can't have loops"));
+        }
+        if let (Loops::Solutions(_), Boundary::Torus) = (loops, options.boundary) {
+            return Err(String::from("a torus has no entry and exit to count the solutions between"));
+        }
+        maze.loops(loops);
+    }
+    if let Some(seed) = options.seed {
+        maze.seed(seed);
+    }
+    if let Some((width, height)) = options.tiles {
+        if options.levels > 1 {
+            return Err(String::from("a maze with several levels can't be generated in tiles"));
+        }
+        maze.tiles(width, height);
+    }
+    let format = match (options.format, options.output.as_ref()) {
+        (Some(format), _) => format,
+        (None, Some(path)) if path.ends_with(".png") => Format::Png,
+        (None, _) => Format::Text
+    };
+    match format {
+        Format::Png => {
+            let path = options.output.ok_or("an --output path is needed for png")?;
+            maze.render(options.scale).save_to_file(&path).map_err(|err| err.to_string())
+        }
+        Format::Text => {
+            if options.levels > 1 {
+                return Err(String::from("a maze with several levels can't be saved as text"));
+            }
+            let text = maze.text();
+            match options.output {
+                Some(path) => text.save_to_file(&path).map_err(|err| err.to_string()),
+                // A closed pipe, as with head, just means nobody wants the rest
+                None => match write!(io::stdout().lock(), "{}", text) {
+                    Err(ref err) if err.kind() != io::ErrorKind::BrokenPipe => Err(err.to_string()),
+                    _ => Ok(())
+                }
+            }
+        }
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let name = match args.first() {
+        Some(name) if !name.starts_with("--") => name,
+        _ => return Err(String::from("the algorithm is missing"))
+    };
+    // Every option that isn't one of the maze is a setting of the algorithm
+    let mut algorithm = name.clone();
+    let mut options = Options {
+        algorithm: Algorithm::RecursiveBacktracking,
+        width: 20,
+        height: 20,
+        levels: 1,
+        boundary: Boundary::Bounded,
+        seed: None,
+        tiles: None,
+        weights: Weights::uniform(),
+        loops: None,
+        scale: 1.0,
+        format: None,
+        output: None
+    };
+    let mut rest = args[1 ..].iter();
+    while let Some(flag) = rest.next() {
+        let value = rest.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag.as_str() {
+            "--width"     => options.width = parse_size(flag, value)?,
+            "--height"    => options.height = parse_size(flag, value)?,
+            "--levels"    => options.levels = parse_size(flag, value)?,
+            "--boundary"  => options.boundary = value.parse().map_err(|err: io::Error| err.to_string())?,
+            "--seed"      => options.seed = Some(parse_number(flag, value)?),
+            "--tiles"     => options.tiles = Some(parse_tiles(value)?),
+            "--weights"   => options.weights = parse_weights(value)?,
+            "--loops"     => options.loops = Some(Loops::Count(parse_number(flag, value)?)),
+            "--solutions" => options.loops = Some(Loops::Solutions(parse_size(flag, value)?)),
+            "--scale"     => options.scale = parse_number(flag, value)?,
+            "--format"    => options.format = Some(parse_format(value)?),
+            "--output"    => options.output = Some(value.clone()),
+            _ if flag.starts_with("--") => algorithm = format!("{} {}={}", algorithm, &flag[2 ..], value),
+            _             => return Err(format!("unknown option '{}'", flag))
+        }
+    }
+    options.algorithm = algorithm.parse().map_err(|err: io::Error| err.to_string())?;
+    if options.scale <= 0.0 {
+        return Err(String::from("--scale must be greater than 0"));
+    }
+    Ok(options)
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got '{}'", flag, value))
+}
+
+// Sizes must be greater than 0
+fn parse_size(flag: &str, value: &str) -> Result<usize, String> {
+    match parse_number(flag, value)? {
+        0    => Err(format!("{} must be greater than 0", flag)),
+        size => Ok(size)
+    }
+}
+
+fn parse_tiles(value: &str) -> Result<(usize, usize), String> {
+    let mut sizes = value.splitn(2, 'x');
+    match (sizes.next(), sizes.next()) {
+        (Some(width), Some(height)) => Ok((parse_size("--tiles", width)?, parse_size("--tiles", height)?)),
+        _                           => Err(format!("--tiles expects WxH, got '{}'", value))
+    }
+}
+
+// The weights of north, south, east and west. Stairs keep a weight of 1.
+fn parse_weights(value: &str) -> Result<Weights, String> {
+    let weights: Vec<usize> = value.split(',')
+        .map(|weight| parse_number("--weights", weight))
+        .collect::<Result<_, _>>()?;
+    match weights[..] {
+        [north, south, east, west] => Ok(Weights { north, south, east, west, stairs: 1 }),
+        _                          => Err(format!("--weights expects N,S,E,W, got '{}'", value))
+    }
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "png"  => Ok(Format::Png),
+        "text" => Ok(Format::Text),
+        _      => Err(format!("unknown format '{}'", value))
+    }
+}
@@ -1,96 +1,92 @@
 use image::{self, DynamicImage, ImageBuffer};
-use image::imageops::FilterType;
 use std::path::Path;
 use std::fs::File;
 use std::io::{Error, ErrorKind};
 use std::io;
-use std::f32::EPSILON;
-use grid::{Cell, Grid, Direction};
+use grid::{Crossing, Grid, Direction};
+use layered::LayeredGrid;
 
-const BASE_STROKE_WIDTH: u32 = 3;
-const BASE_CELL_LENGTH: u32 = 15;
-const MARGIN_LENGTH: u32 = 15;
+pub const BASE_STROKE_WIDTH: u32 = 3;
+pub const BASE_CELL_LENGTH: u32 = 15;
+pub const MARGIN_LENGTH: u32 = 15;
 const BLACK: u8 = 0;
 const WHITE: u8 = 255;
 
 pub struct MazeRender {
-    width: u32,
-    height: u32,
+    // A regular maze has a single level
+    levels: Vec<Grid>,
     scale: f32,
-    cells: Vec<Vec<Cell>>,
     img: Option<DynamicImage>
 }
 
 impl MazeRender {
     pub fn new(grid: &Grid) -> MazeRender {
         MazeRender {
-            width: grid.width as u32,
-            height: grid.height as u32,
+            levels: vec![grid.clone()],
             scale: 1.0,
-            cells: grid.cells.clone(),
             img: None
         }
     }
 
+    // The levels are drawn side by side, from the bottom one to the top one
+    pub fn from_layers(grid: &LayeredGrid) -> MazeRender {
+        MazeRender {
+            levels: grid.levels.clone(),
+            scale: 1.0,
+            img: None
+        }
+    }
+
+    // The maze is rasterized directly at the scaled geometry, so the output
+    // stays two-colored at every scale. Lengths are rounded to whole pixels.
     pub fn scale(&mut self, scale: f32) -> &mut MazeRender {
         self.scale = scale;
         self
     }
 
+    pub fn image(&self) -> Option<&DynamicImage> {
+        self.img.as_ref()
+    }
+
     pub fn render(&mut self) -> &mut MazeRender {
-        let img_width = MARGIN_LENGTH * 2 + 
-            self.width * (BASE_CELL_LENGTH + BASE_STROKE_WIDTH) + 
-            BASE_STROKE_WIDTH;
-        let img_height = MARGIN_LENGTH * 2 + 
-            self.height * (BASE_CELL_LENGTH + BASE_STROKE_WIDTH) + 
-            BASE_STROKE_WIDTH;
+        let stroke_width = scaled(BASE_STROKE_WIDTH, self.scale, 1);
+        let cell_length = scaled(BASE_CELL_LENGTH, self.scale, 1);
+        let margin_length = scaled(MARGIN_LENGTH, self.scale, 0);
+        let level_width = self.levels[0].width as u32 * (cell_length + stroke_width) + 
+            stroke_width;
+        let level_height = self.levels[0].height as u32 * (cell_length + stroke_width) + 
+            stroke_width;
+        let levels = self.levels.len() as u32;
+        // The levels are a margin apart
+        let img_width = margin_length * 2 + levels * level_width + (levels - 1) * margin_length;
+        let img_height = margin_length * 2 + level_height;
         let mut imgbuf = ImageBuffer::new(img_width, img_height);
         // Iterate over the coordiantes and pixels of the image
         for (x, y, pixel) in imgbuf.enumerate_pixels_mut() {
             let mut value = image::Luma([WHITE]);
-            let inside_margins = x >= MARGIN_LENGTH && x < (img_width - MARGIN_LENGTH) &&
-                y >= MARGIN_LENGTH && y < (img_height - MARGIN_LENGTH);
+            let inside_margins = x >= margin_length && x < (img_width - margin_length) &&
+                y >= margin_length && y < (img_height - margin_length);
             if inside_margins {
-                let xt = x - MARGIN_LENGTH;
-                let yt = y - MARGIN_LENGTH;
-                let cell_len = BASE_CELL_LENGTH + BASE_STROKE_WIDTH;
-                let x_in_stroke = xt % cell_len < BASE_STROKE_WIDTH;
-                let y_in_stroke = yt % cell_len < BASE_STROKE_WIDTH;
-                let mut x_dir = Direction::West;
-                let mut y_dir = Direction::North;
-                let mut x_idx = (xt / cell_len) as usize;
-                let mut y_idx = (yt / cell_len) as usize;
-                let mut border = false;
-                // we treat the case where we hit the last wall of the maze
-                if x_idx == self.width as usize {
-                    x_idx -= 1;
-                    x_dir = Direction::East;
-                    border = true;
-                }
-                if y_idx == self.height as usize {
-                    y_idx -= 1;
-                    y_dir = Direction::South;
-                    border = true;
-                }
-                // corner case :)
-                let corner_closed = !border && x_in_stroke && y_in_stroke &&
-                    ((x_idx > 0 && self.cells[x_idx - 1][y_idx].get(y_dir)) ||
-                    (y_idx > 0 && self.cells[x_idx][y_idx - 1].get(x_dir)));                                
-                let x_cell_closed = x_in_stroke && self.cells[x_idx][y_idx].get(x_dir);
-                let y_cell_closed = y_in_stroke && self.cells[x_idx][y_idx].get(y_dir);
-                if x_cell_closed || y_cell_closed || corner_closed {
+                let level = (x - margin_length) / (level_width + margin_length);
+                let xt = (x - margin_length) % (level_width + margin_length);
+                let yt = y - margin_length;
+                let grid = &self.levels[level as usize];
+                // Crossings can't be shown with walls on the cell borders
+                let closed = if xt >= level_width {
+                    false
+                } else if grid.has_crossings() {
+                    inset_closed(grid, xt, yt, cell_length, stroke_width)
+                } else {
+                    stroke_closed(grid, xt, yt, cell_length, stroke_width) || 
+                        on_stairs(grid, xt, yt, cell_length, stroke_width)
+                };
+                if closed {
                     value = image::Luma([BLACK]);
                 }
             }
             *pixel = value;           
         }
-        let mut img = DynamicImage::ImageLuma8(imgbuf);
-        if (self.scale - 1.0).abs() > EPSILON {
-            let new_width = (img_width as f32 * self.scale) as u32;
-            let new_height = (img_height as f32 * self.scale) as u32;
-            img = img.resize_exact(new_width, new_height, FilterType::Lanczos3);
-        }
-        self.img = Some(img);
+        self.img = Some(DynamicImage::ImageLuma8(imgbuf));
         self
     }
 
@@ -119,3 +115,99 @@ impl MazeRender {
         Ok(())
     }
 }
+
+// Scale a base length to whole pixels, never going below the given minimum
+pub fn scaled(length: u32, scale: f32, min: u32) -> u32 {
+    let length = (length as f32 * scale).round() as u32;
+    length.max(min)
+}
+
+// Whether the pixel at (xt, yt), relative to the level's margins, is on a wall
+fn stroke_closed(grid: &Grid, xt: u32, yt: u32, cell_length: u32, stroke_width: u32) -> bool {
+    let cell_len = cell_length + stroke_width;
+    let x_in_stroke = xt % cell_len < stroke_width;
+    let y_in_stroke = yt % cell_len < stroke_width;
+    let mut x_dir = Direction::West;
+    let mut y_dir = Direction::North;
+    let mut x_idx = (xt / cell_len) as usize;
+    let mut y_idx = (yt / cell_len) as usize;
+    let mut border = false;
+    // we treat the case where we hit the last wall of the maze
+    if x_idx == grid.width {
+        x_idx -= 1;
+        x_dir = Direction::East;
+        border = true;
+    }
+    if y_idx == grid.height {
+        y_idx -= 1;
+        y_dir = Direction::South;
+        border = true;
+    }
+    // corner case :)
+    let corner_closed = !border && x_in_stroke && y_in_stroke &&
+        ((x_idx > 0 && grid.has_wall(x_idx - 1, y_idx, y_dir)) ||
+        (y_idx > 0 && grid.has_wall(x_idx, y_idx - 1, x_dir)));                                
+    let x_cell_closed = x_in_stroke && grid.has_wall(x_idx, y_idx, x_dir);
+    let y_cell_closed = y_in_stroke && grid.has_wall(x_idx, y_idx, y_dir);
+    x_cell_closed || y_cell_closed || corner_closed
+}
+
+// Same as stroke_closed, but every cell is drawn inset in its square. The passage
+// over a crossing keeps its walls along the whole cell, while the walls of the
+// passage under it stop short, leaving a gap on each side.
+fn inset_closed(grid: &Grid, xt: u32, yt: u32, cell_length: u32, stroke_width: u32) -> bool {
+    let cell_len = cell_length + stroke_width;
+    let x_idx = (xt / cell_len) as usize;
+    let y_idx = (yt / cell_len) as usize;
+    // The trailing stroke of the regular layout stays blank
+    if x_idx == grid.width || y_idx == grid.height {
+        return false;
+    }
+    let (xc, yc) = (xt % cell_len, yt % cell_len);
+    // The room of a cell spans [low, high) on both axes, its walls are right outside.
+    // The inset leaves a gap of about half a stroke around them.
+    let low = inset(cell_length, stroke_width);
+    let high = cell_len - low;
+    let before = |v: u32| v + stroke_width >= low && v < low;
+    let after = |v: u32| v >= high && v < high + stroke_width;
+    let sides = |v: u32| before(v) || after(v);
+    let across = |v: u32| v + stroke_width >= low && v < high + stroke_width;
+    match grid.crossing(x_idx, y_idx) {
+        Some(Crossing::Horizontal) => sides(xc),
+        Some(Crossing::Vertical)   => sides(yc),
+        None => {
+            // A closed side is a wall across the room, an open one extends
+            // the walls of the room to the edge of the cell
+            let cell = grid.cell(x_idx, y_idx);
+            let north = if cell.get(Direction::North) { before(yc) && across(xc) } else { yc < low && sides(xc) };
+            let south = if cell.get(Direction::South) { after(yc) && across(xc) } else { yc >= high && sides(xc) };
+            let west = if cell.get(Direction::West) { before(xc) && across(yc) } else { xc < low && sides(yc) };
+            let east = if cell.get(Direction::East) { after(xc) && across(yc) } else { xc >= high && sides(yc) };
+            north || south || west || east
+        }
+    }
+}
+
+// Where the room of an inset cell starts, relative to the cell's square
+pub fn inset(cell_length: u32, stroke_width: u32) -> u32 {
+    (stroke_width + stroke_width / 2 + 1).min((cell_length + stroke_width) / 3)
+}
+
+// Stairs are marked by a triangle pointing up on the upper half of the cell, and
+// one pointing down on the lower half
+fn on_stairs(grid: &Grid, xt: u32, yt: u32, cell_length: u32, stroke_width: u32) -> bool {
+    let cell_len = cell_length + stroke_width;
+    let x_idx = (xt / cell_len) as usize;
+    let y_idx = (yt / cell_len) as usize;
+    let (xc, yc) = (xt % cell_len, yt % cell_len);
+    if x_idx == grid.width || y_idx == grid.height || xc < stroke_width || yc < stroke_width {
+        return false;
+    }
+    // Relative to the inside of the cell
+    let (xc, yc) = ((xc - stroke_width) as i32, (yc - stroke_width) as i32);
+    let (len, top) = (cell_length as i32, cell_length as i32 / 6);
+    let middle = len / 2;
+    let triangle = |yc: i32| yc >= top && yc < middle && 2 * (xc - middle).abs() <= yc - top;
+    let cell = grid.cell(x_idx, y_idx);
+    (!cell.get(Direction::Up) && triangle(yc)) || (!cell.get(Direction::Down) && triangle(len - 1 - yc))
+}
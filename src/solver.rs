@@ -0,0 +1,552 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use grid::{Boundary, Direction, Grid, PLANAR};
+
+// The cells with an opening in the outer walls, which are the entry and exit points
+// of the maze. A torus has no outer walls, so it has none.
+pub fn entry_points(grid: &Grid) -> Vec<(usize, usize)> {
+    let mut points = Vec::new();
+    for x in 0 .. grid.width {
+        for y in 0 .. grid.height {
+            let opening = PLANAR.iter().any(|&dir| {
+                grid.has_border(dir) && grid.cell_at(x, y, dir).is_none() && !grid.has_wall(x, y, dir)
+            });
+            if opening {
+                points.push((x, y));
+            }
+        }
+    }
+    points
+}
+
+// The shortest path from one cell to another, both included, found with a breadth
+// first search. Passages go straight through crossings, so they are never on the path.
+pub fn shortest_path(grid: &Grid, from: (usize, usize), to: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+    let index = |(x, y): (usize, usize)| x * grid.height + y;
+    let mut previous = vec![None; grid.width * grid.height];
+    let mut queue = VecDeque::new();
+    previous[index(from)] = Some(from);
+    queue.push_back(from);
+    while let Some((x, y)) = queue.pop_front() {
+        if (x, y) == to {
+            break;
+        }
+        for &dir in &PLANAR {
+            if let Some(next) = grid.passage(x, y, dir) {
+                if previous[index(next)].is_none() {
+                    previous[index(next)] = Some((x, y));
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    previous[index(to)]?;
+    // Walk back from the end
+    let mut path = vec![to];
+    let mut pos = to;
+    while pos != from {
+        pos = previous[index(pos)].unwrap();
+        path.push(pos);
+    }
+    path.reverse();
+    Some(path)
+}
+
+// The number of ways from one cell to another that never visit a cell twice, counted up
+// to the limit. A limit of 2 tells whether a maze has a single solution.
+pub fn count_solutions(grid: &Grid, from: (usize, usize), to: (usize, usize), limit: usize) -> usize {
+    let mut count = 0;
+    if limit > 0 {
+        Junctions::new(grid, from, to).search(&mut |_| {
+            count += 1;
+            count < limit
+        });
+    }
+    count
+}
+
+// The ways from one cell to another that never visit a cell twice, both cells included,
+// up to the limit. They come in no particular order.
+pub fn solutions(grid: &Grid, from: (usize, usize), to: (usize, usize), limit: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut paths = Vec::new();
+    if limit > 0 {
+        let junctions = Junctions::new(grid, from, to);
+        junctions.search(&mut |steps| {
+            paths.push(junctions.cells(steps));
+            paths.len() < limit
+        });
+    }
+    paths
+}
+
+// The k shortest ways from one cell to another that never visit a cell twice, from the
+// shortest, found with Yen's algorithm over the junctions. Ways of the same length come
+// in no particular order.
+pub fn shortest_paths(grid: &Grid, from: (usize, usize), to: (usize, usize), k: usize) -> Vec<Vec<(usize, usize)>> {
+    let junctions = Junctions::new(grid, from, to);
+    let mut found: Vec<Vec<Step>> = Vec::new();
+    let mut candidates = BinaryHeap::new();
+    let mut seen = HashSet::new();
+    let nothing_removed = (HashSet::new(), vec![false; junctions.nodes.len()]);
+    if let Some(first) = junctions.shortest(0, &nothing_removed.0, &nothing_removed.1) {
+        seen.insert(first.clone());
+        candidates.push(Reverse((junctions.length(&first), first)));
+    }
+    while found.len() < k {
+        match candidates.pop() {
+            Some(Reverse((_, path))) => found.push(path),
+            None                     => break
+        }
+        if found.len() == k {
+            break;
+        }
+        // Leave the last path at every junction of it, by a way none of the paths found so
+        // far with the same start took
+        let path = &found[found.len() - 1];
+        for i in 0 .. path.len() {
+            let (root, spur) = (&path[.. i], path[i].0);
+            let removed_steps = found.iter()
+                .filter(|other| other.len() > i && other[.. i] == *root)
+                .map(|other| other[i])
+                .collect();
+            let mut removed_nodes = vec![false; junctions.nodes.len()];
+            for &(node, _) in root {
+                removed_nodes[node] = true;
+            }
+            if let Some(rest) = junctions.shortest(spur, &removed_steps, &removed_nodes) {
+                let next: Vec<Step> = root.iter().cloned().chain(rest).collect();
+                if seen.insert(next.clone()) {
+                    candidates.push(Reverse((junctions.length(&next), next)));
+                }
+            }
+        }
+    }
+    found.iter().map(|steps| junctions.cells(steps)).collect()
+}
+
+// The cells every way from one cell to another passes through, in the order they're met,
+// without the two cells. They're the cells that cut the maze in two, found with Tarjan's
+// depth first search. None if there's no way at all.
+pub fn articulation_cells(grid: &Grid, from: (usize, usize), to: (usize, usize)) -> Option<Vec<(usize, usize)>> {
+    let index = |(x, y): (usize, usize)| x * grid.height + y;
+    // The order the cells are found in, and the earliest cell a cell's subtree reaches back to
+    let mut found = vec![None; grid.width * grid.height];
+    let mut low = vec![0; grid.width * grid.height];
+    let mut parents = vec![None; grid.width * grid.height];
+    let mut stack = vec![(from, 0)];
+    found[index(from)] = Some(0);
+    let mut time = 1;
+    while let Some(&mut (pos, ref mut next_dir)) = stack.last_mut() {
+        if let Some(&dir) = PLANAR.get(*next_dir) {
+            *next_dir += 1;
+            if let Some(next) = grid.passage(pos.0, pos.1, dir) {
+                match found[index(next)] {
+                    Some(order) => low[index(pos)] = low[index(pos)].min(order),
+                    None        => {
+                        found[index(next)] = Some(time);
+                        low[index(next)] = time;
+                        parents[index(next)] = Some(pos);
+                        time += 1;
+                        stack.push((next, 0));
+                    }
+                }
+            }
+        } else {
+            stack.pop();
+            if let Some(parent) = parents[index(pos)] {
+                low[index(parent)] = low[index(parent)].min(low[index(pos)]);
+            }
+        }
+    }
+    found[index(to)]?;
+    // A cell on the way back from the end cuts it off when the subtree below it can't reach
+    // further back than the cell itself
+    let mut cells = Vec::new();
+    let mut child = to;
+    while let Some(parent) = parents[index(child)] {
+        if parent != from && low[index(child)] >= found[index(parent)].unwrap() {
+            cells.push(parent);
+        }
+        child = parent;
+    }
+    cells.reverse();
+    Some(cells)
+}
+
+// The way one of the human-style solvers went through the maze
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Walk {
+    // Every cell it stepped on in order, backtracks included. Dead-end filling has the
+    // cells it filled instead.
+    pub steps: Vec<(usize, usize)>,
+    // The way it found from the start to the end, without its detours
+    pub path: Vec<(usize, usize)>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hand {
+    Left,
+    Right
+}
+
+// Keep a hand on the wall: turn to its side when there's a passage, or go straight, or
+// turn the other way, or go back. It comes in from the outer opening of the start when
+// there's one. None if it walks in circles without reaching the end, which happens when
+// the end isn't on the same wall as the start.
+pub fn wall_follower(grid: &Grid, from: (usize, usize), to: (usize, usize), hand: Hand) -> Option<Walk> {
+    let index = |(x, y): (usize, usize)| x * grid.height + y;
+    let mut walker = Walker::new(grid, from);
+    // Every cell, heading and side of a Moebius strip it has been at
+    let mut seen = vec![false; grid.width * grid.height * 8];
+    while walker.pos != to {
+        let state = index(walker.pos) * 8 + walker.heading * 2 + walker.flipped as usize;
+        if seen[state] {
+            return None;
+        }
+        seen[state] = true;
+        let side = walker.side(hand);
+        let heading = walker.heading;
+        let turns = [side, heading, BACK[side], BACK[heading]];
+        let dir = turns.iter().cloned().find(|&dir| walker.can_step(dir))?;
+        walker.step(dir);
+    }
+    Some(walker.walk())
+}
+
+// Trémaux's algorithm: mark the entrances to the cells on the way in and out. In a new
+// cell take an entrance without marks, and go back when the cell was there before, unless
+// the way back has two marks already. Otherwise take the entrance with the fewest marks,
+// never one with two. The entrances are tried north, south, east then west. None if
+// there's no way to the end.
+pub fn tremaux(grid: &Grid, from: (usize, usize), to: (usize, usize)) -> Option<Walk> {
+    let index = |(x, y): (usize, usize)| x * grid.height + y;
+    let mut marks = vec![[0; 4]; grid.width * grid.height];
+    let mut walker = Walker::new(grid, from);
+    let mut entrance = None;
+    while walker.pos != to {
+        let cell = index(walker.pos);
+        let open: Vec<usize> = (0 .. 4).filter(|&dir| walker.can_step(dir)).collect();
+        let fresh = open.iter().all(|&dir| Some(dir) == entrance || marks[cell][dir] == 0);
+        let dir = match entrance {
+            Some(back) if !fresh && marks[cell][back] < 2 => back,
+            _ => *open.iter().filter(|&&dir| marks[cell][dir] < 2 && Some(dir) != entrance)
+                .min_by_key(|&&dir| marks[cell][dir])
+                .or_else(|| entrance.iter().find(|&&back| marks[cell][back] < 2))?
+        };
+        marks[cell][dir] += 1;
+        walker.step(dir);
+        let back = BACK[walker.heading];
+        marks[index(walker.pos)][back] += 1;
+        entrance = Some(back);
+    }
+    Some(walker.walk())
+}
+
+// Fill every dead end back to its junction, until only the ways from the start to the end
+// are left. None if there's no way to the end.
+pub fn dead_end_filling(grid: &Grid, from: (usize, usize), to: (usize, usize)) -> Option<Walk> {
+    let (steps, _, _) = fill_dead_ends(grid, from, to);
+    let path = shortest_path(grid, from, to)?;
+    Some(Walk { steps, path })
+}
+
+// The Pledge algorithm: go straight towards the heading until there's a wall, then follow
+// it with the left hand, counting the turns. Once the turns add up to none, leave the wall
+// and go straight again. It finds the end when the end is on the outer wall, unless the
+// passages cross each other around loops. It gives up after a few visits of every cell.
+pub fn pledge(grid: &Grid, from: (usize, usize), to: (usize, usize), heading: Direction) -> Option<Walk> {
+    let mut walker = Walker::new(grid, from);
+    walker.heading = match heading {
+        Direction::North => 0,
+        Direction::South => 1,
+        Direction::East  => 2,
+        Direction::West  => 3,
+        Direction::Up | Direction::Down => panic!("The Pledge algorithm can only head north, south, east or west.")
+    };
+    // Quarter turns to the left, the wall is followed while it isn't 0
+    let mut turns: isize = 0;
+    let mut following = false;
+    let most = 32 * grid.width * grid.height;
+    while walker.pos != to {
+        if walker.steps.len() > most {
+            return None;
+        }
+        if !following {
+            if walker.can_step(walker.heading) {
+                let dir = walker.heading;
+                walker.step(dir);
+                continue;
+            }
+            // Turn right, to put the left hand on the wall
+            walker.heading = walker.side(Hand::Right);
+            turns -= 1;
+        }
+        let (left, ahead) = (walker.side(Hand::Left), walker.heading);
+        let right = walker.side(Hand::Right);
+        let (dir, turn) = [(left, 1), (ahead, 0), (right, -1), (BACK[ahead], -2)].iter().cloned()
+            .find(|&(dir, _)| walker.can_step(dir))?;
+        walker.step(dir);
+        turns += turn;
+        following = turns != 0;
+    }
+    Some(walker.walk())
+}
+
+// The directions back, by their place in PLANAR
+const BACK: [usize; 4] = [1, 0, 3, 2];
+// The directions to the left, by their place in PLANAR
+const LEFT: [usize; 4] = [3, 2, 0, 1];
+
+// Walks the maze one passage at a time. The heading is kept through the passages, but the
+// sides swap past the edge of a Moebius strip.
+struct Walker<'a> {
+    grid: &'a Grid,
+    pos: (usize, usize),
+    // The place of the heading in PLANAR
+    heading: usize,
+    flipped: bool,
+    steps: Vec<(usize, usize)>
+}
+
+impl<'a> Walker<'a> {
+    // Facing into the maze from the outer opening of the cell, or north
+    fn new(grid: &'a Grid, from: (usize, usize)) -> Walker<'a> {
+        let (x, y) = from;
+        let opening = PLANAR.iter().position(|&dir| {
+            grid.has_border(dir) && grid.cell_at(x, y, dir).is_none() && !grid.has_wall(x, y, dir)
+        });
+        Walker { grid, pos: from, heading: opening.map_or(0, |dir| BACK[dir]), flipped: false, steps: vec![from] }
+    }
+
+    // The direction to the left or the right of the heading
+    fn side(&self, hand: Hand) -> usize {
+        if (hand == Hand::Left) != self.flipped {
+            LEFT[self.heading]
+        } else {
+            BACK[LEFT[self.heading]]
+        }
+    }
+
+    fn can_step(&self, dir: usize) -> bool {
+        self.grid.passage(self.pos.0, self.pos.1, PLANAR[dir]).is_some()
+    }
+
+    // Through the passage, and over the crossings on the way
+    fn step(&mut self, dir: usize) {
+        let (mut x, mut y) = self.pos;
+        loop {
+            let seam = match PLANAR[dir] {
+                Direction::East => x == self.grid.width - 1,
+                Direction::West => x == 0,
+                _               => false
+            };
+            if seam && self.grid.boundary() == Boundary::Moebius {
+                self.flipped = !self.flipped;
+            }
+            let (nx, ny) = self.grid.cell_at(x, y, PLANAR[dir]).unwrap();
+            x = nx;
+            y = ny;
+            if self.grid.crossing(x, y).is_none() {
+                break;
+            }
+        }
+        self.pos = (x, y);
+        self.heading = dir;
+        self.steps.push(self.pos);
+    }
+
+    fn walk(self) -> Walk {
+        let path = erase_loops(&self.steps);
+        Walk { steps: self.steps, path }
+    }
+}
+
+// The walk without the loops it made, cut out as soon as a cell comes up again
+fn erase_loops(steps: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut path: Vec<(usize, usize)> = Vec::new();
+    let mut places = HashMap::new();
+    for &pos in steps {
+        if let Some(&place) = places.get(&pos) {
+            for cell in path.drain(place + 1 ..) {
+                places.remove(&cell);
+            }
+        } else {
+            places.insert(pos, path.len());
+            path.push(pos);
+        }
+    }
+    path
+}
+
+// Fill the dead ends, and the corridors that become dead ends, until every cell left has at
+// least two passages or is one of the two cells. The cells in the order they're filled,
+// which cells are filled, and the passages left to every cell.
+fn fill_dead_ends(grid: &Grid, from: (usize, usize), to: (usize, usize))
+    -> (Vec<(usize, usize)>, Vec<bool>, Vec<usize>) {
+    let index = |(x, y): (usize, usize)| x * grid.height + y;
+    let passages = |(x, y): (usize, usize)| PLANAR.iter().filter_map(move |&dir| grid.passage(x, y, dir));
+    let is_end = |pos| pos == from || pos == to;
+    let mut degrees = vec![0; grid.width * grid.height];
+    let mut dead_ends = Vec::new();
+    for x in 0 .. grid.width {
+        for y in 0 .. grid.height {
+            if grid.crossing(x, y).is_none() {
+                degrees[index((x, y))] = passages((x, y)).count();
+                if degrees[index((x, y))] < 2 && !is_end((x, y)) {
+                    dead_ends.push((x, y));
+                }
+            }
+        }
+    }
+    let mut order = Vec::new();
+    let mut filled = vec![false; grid.width * grid.height];
+    // The last dead end found is filled first, so a corridor is filled all the way back
+    while let Some(pos) = dead_ends.pop() {
+        filled[index(pos)] = true;
+        order.push(pos);
+        for next in passages(pos).filter(|&next| !filled[index(next)]) {
+            degrees[index(next)] -= 1;
+            if degrees[index(next)] == 1 && !is_end(next) {
+                dead_ends.push(next);
+            }
+        }
+    }
+    (order, filled, degrees)
+}
+
+// A step from a junction through one of its corridors
+type Step = (usize, usize);
+
+// The junction at the other end of a corridor, and its cells up to that junction
+type Corridor = (usize, Vec<(usize, usize)>);
+
+// The ways between two cells once the dead ends are filled: the junctions, and the corridors
+// between them, followed in a single step. Only the junctions are searched.
+struct Junctions {
+    nodes: Vec<(usize, usize)>,
+    // The junction of the end cell
+    end: usize,
+    // The corridors out of every junction
+    corridors: Vec<Vec<Corridor>>
+}
+
+impl Junctions {
+    // The first junction is the start and the second one the end, unless they're the same cell
+    fn new(grid: &Grid, from: (usize, usize), to: (usize, usize)) -> Junctions {
+        let index = |(x, y): (usize, usize)| x * grid.height + y;
+        let passages = |(x, y): (usize, usize)| PLANAR.iter().filter_map(move |&dir| grid.passage(x, y, dir));
+        let is_end = |pos| pos == from || pos == to;
+        let (_, filled, degrees) = fill_dead_ends(grid, from, to);
+        let mut nodes = vec![from];
+        if to != from {
+            nodes.push(to);
+        }
+        for x in 0 .. grid.width {
+            for y in 0 .. grid.height {
+                if grid.crossing(x, y).is_none() && !filled[index((x, y))] && degrees[index((x, y))] > 2 &&
+                    !is_end((x, y)) {
+                    nodes.push((x, y));
+                }
+            }
+        }
+        let mut ids = vec![None; grid.width * grid.height];
+        for (id, &pos) in nodes.iter().enumerate() {
+            ids[index(pos)] = Some(id);
+        }
+        let corridors = nodes.iter().map(|&node| {
+            passages(node).filter(|&next| !filled[index(next)]).map(|mut pos| {
+                let (mut previous, mut cells) = (node, vec![pos]);
+                while ids[index(pos)].is_none() {
+                    // The passage back is skipped once, two passages can join the same cells
+                    let mut back = false;
+                    let next = passages(pos).filter(|&next| !filled[index(next)])
+                        .find(|&next| next != previous || std::mem::replace(&mut back, true))
+                        .unwrap();
+                    previous = pos;
+                    pos = next;
+                    cells.push(pos);
+                }
+                (ids[index(pos)].unwrap(), cells)
+            }).collect()
+        }).collect();
+        Junctions { nodes, end: if to == from { 0 } else { 1 }, corridors }
+    }
+
+    // Call found with the steps of every way from the start to the end, until it returns false
+    fn search(&self, found: &mut dyn FnMut(&[Step]) -> bool) {
+        let mut visited = vec![false; self.nodes.len()];
+        self.search_from(0, &mut visited, &mut Vec::new(), found);
+    }
+
+    fn search_from(&self, node: usize, visited: &mut [bool], steps: &mut Vec<Step>,
+        found: &mut dyn FnMut(&[Step]) -> bool) -> bool {
+        if node == self.end {
+            return found(steps);
+        }
+        visited[node] = true;
+        let mut more = true;
+        for (corridor, &(next, _)) in self.corridors[node].iter().enumerate() {
+            if !visited[next] {
+                steps.push((node, corridor));
+                more = self.search_from(next, visited, steps, found);
+                steps.pop();
+                if !more {
+                    break;
+                }
+            }
+        }
+        visited[node] = false;
+        more
+    }
+
+    // The shortest way from a junction to the end, with Dijkstra's algorithm, that doesn't
+    // take the removed steps or go through the removed junctions
+    fn shortest(&self, start: usize, removed_steps: &HashSet<Step>, removed_nodes: &[bool]) -> Option<Vec<Step>> {
+        let mut distances = vec![usize::MAX; self.nodes.len()];
+        let mut previous: Vec<Option<Step>> = vec![None; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        distances[start] = 0;
+        queue.push(Reverse((0, start)));
+        while let Some(Reverse((distance, node))) = queue.pop() {
+            if distance > distances[node] {
+                continue;
+            }
+            if node == self.end {
+                break;
+            }
+            for (corridor, &(next, ref cells)) in self.corridors[node].iter().enumerate() {
+                let longer = distance + cells.len();
+                if !removed_nodes[next] && !removed_steps.contains(&(node, corridor)) && longer < distances[next] {
+                    distances[next] = longer;
+                    previous[next] = Some((node, corridor));
+                    queue.push(Reverse((longer, next)));
+                }
+            }
+        }
+        if distances[self.end] == usize::MAX {
+            return None;
+        }
+        let mut steps = Vec::new();
+        let mut node = self.end;
+        while node != start {
+            let step = previous[node].unwrap();
+            steps.push(step);
+            node = step.0;
+        }
+        steps.reverse();
+        Some(steps)
+    }
+
+    fn length(&self, steps: &[Step]) -> usize {
+        steps.iter().map(|&(node, corridor)| self.corridors[node][corridor].1.len()).sum()
+    }
+
+    // The cells of a way, from the start
+    fn cells(&self, steps: &[Step]) -> Vec<(usize, usize)> {
+        let mut cells = vec![self.nodes[0]];
+        for &(node, corridor) in steps {
+            cells.extend_from_slice(&self.corridors[node][corridor].1);
+        }
+        cells
+    }
+}
@@ -1,5 +1,16 @@
-use super::Maze;
-use algorithms::{Algorithm, CellSelection, Bias, Scan};
+use std::collections::HashSet;
+use rand::{self, Rng, StdRng};
+use super::{Either, Farthest, Loops, Maze, OriginShift, Random, Region, Selection};
+use algorithms::{Algorithm, Automaton, CellSelection, Bias, Division, Generator, Hunt, Prims, Rule, Scan, Weights};
+use algorithms;
+use algorithms::{choose_entry_points, ellers_algorithm, kruskals_algorithm, recursive_backtracking, tiled,
+    weave_kruskals_algorithm};
+use grid::{Boundary, Carve, Crossing, Direction, Grid, PLANAR, SPATIAL};
+use layered::LayeredGrid;
+use text::MazeText;
+use import::MazeImport;
+use solver;
+use image::GenericImage;
 
 const WIDTH: usize = 100;
 const HEIGHT: usize = 50;
@@ -41,6 +52,30 @@ fn create_kruskals_algorithm() {
         .render(RENDER_SCALE);
 }
 
+#[test]
+fn kruskals_algorithm_is_perfect() {
+    use grid::Direction;
+    // Every cell is reached, the last row and column included, through one passage less than there are cells
+    let grid = Maze::new(WIDTH, HEIGHT).algorithm(Algorithm::KruskalsAlgorithm).generate();
+    let directions = [Direction::North, Direction::South, Direction::East, Direction::West];
+    let mut seen = vec![vec![false; HEIGHT]; WIDTH];
+    let mut stack = vec![(0, 0)];
+    let mut passages = 0;
+    while let Some((x, y)) = stack.pop() {
+        if !seen[x][y] {
+            seen[x][y] = true;
+            for &dir in directions.iter() {
+                if let Some(next) = grid.cell_at(x, y, dir).filter(|_| !grid.has_wall(x, y, dir)) {
+                    passages += 1;
+                    stack.push(next);
+                }
+            }
+        }
+    }
+    assert!(seen.iter().all(|column| column.iter().all(|&seen| seen)));
+    assert_eq!(passages / 2, WIDTH * HEIGHT - 1);
+}
+
 #[test]
 fn create_prims_algorithm() {
     let _ = Maze::new(WIDTH, HEIGHT)
@@ -48,6 +83,21 @@ fn create_prims_algorithm() {
         .render(RENDER_SCALE);
 }
 
+#[test]
+fn prims_variants() {
+    for &prims in &[Prims::Simplified, Prims::Cells, Prims::Edges] {
+        for &boundary in &[Boundary::Bounded, Boundary::Moebius] {
+            let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, boundary);
+            let algorithm = Algorithm::PrimsAlgorithmWith(prims);
+            algorithms::carve(&mut grid, &mut rand::thread_rng(), algorithm, Weights::uniform());
+            assert_perfect(&grid);
+        }
+        // The same seed gives the same maze
+        let text = |seed| Maze::new(WIDTH, HEIGHT).algorithm(Algorithm::PrimsAlgorithmWith(prims)).seed(seed).text();
+        assert_eq!(text(7).to_string(), text(7).to_string());
+    }
+}
+
 #[test]
 fn create_recursive_division() {
     let _ = Maze::new(WIDTH, HEIGHT)
@@ -55,6 +105,118 @@ fn create_recursive_division() {
         .render(RENDER_SCALE);
 }
 
+#[test]
+fn recursive_division_rooms() {
+    let division = Division { room_size: 4, centering: 50, passages: 2 };
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.algorithm(Algorithm::RecursiveDivisionWith(division)).seed(3);
+    let grid = maze.generate();
+    let mut passages = 0;
+    for x in 0 .. WIDTH {
+        for y in 0 .. HEIGHT {
+            passages += (x + 1 < WIDTH && !grid.has_wall(x, y, Direction::East)) as usize;
+            passages += (y + 1 < HEIGHT && !grid.has_wall(x, y, Direction::South)) as usize;
+        }
+    }
+    // The rooms and the extra passages add loops, but every cell is still reachable
+    assert!(passages > WIDTH * HEIGHT - 1);
+    let path = solver::shortest_path(&grid, (0, 0), (WIDTH - 1, HEIGHT - 1));
+    assert!(path.is_some());
+    let open = |x: usize, y: usize| !grid.has_wall(x, y, Direction::East) && !grid.has_wall(x, y, Direction::South);
+    assert!((0 .. WIDTH - 1).any(|x| (0 .. HEIGHT - 1).any(|y| open(x, y) && open(x + 1, y + 1))));
+    // The settings are saved with the maze
+    let parsed = MazeText::parse(&maze.text().to_string()).unwrap();
+    match parsed.algorithm {
+        Some(Algorithm::RecursiveDivisionWith(parsed)) => assert_eq!(parsed, division),
+        _ => panic!("The algorithm wasn't parsed")
+    }
+}
+
+#[test]
+fn blobby_division_region_pieces() {
+    // The two halves of the region don't touch, regions join them
+    let halves = (0 .. WIDTH).filter(|&x| x != WIDTH / 2).flat_map(|x| (0 .. HEIGHT).map(move |y| (x, y)));
+    let grid = Maze::new(WIDTH, HEIGHT)
+        .region(Region::masked(halves, Algorithm::BlobbyDivision(1)))
+        .generate();
+    assert_perfect(&grid);
+}
+
+#[test]
+fn blobby_division_is_perfect() {
+    for &boundary in &[Boundary::Bounded, Boundary::Moebius] {
+        let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, boundary);
+        algorithms::carve(&mut grid, &mut rand::thread_rng(), Algorithm::BlobbyDivision(1), Weights::uniform());
+        assert_perfect(&grid);
+    }
+    // Rooms add loops
+    let grid = Maze::new(WIDTH, HEIGHT).algorithm(Algorithm::BlobbyDivision(20)).generate();
+    let mut passages = 0;
+    for x in 0 .. WIDTH {
+        for y in 0 .. HEIGHT {
+            passages += (x + 1 < WIDTH && !grid.has_wall(x, y, Direction::East)) as usize;
+            passages += (y + 1 < HEIGHT && !grid.has_wall(x, y, Direction::South)) as usize;
+        }
+    }
+    assert!(passages > WIDTH * HEIGHT - 1);
+    assert!(solver::shortest_path(&grid, (0, 0), (WIDTH - 1, HEIGHT - 1)).is_some());
+}
+
+#[test]
+fn cellular_automaton_is_connected() {
+    for &rule in &[Rule::Maze, Rule::Mazectric] {
+        let mut maze = Maze::new(WIDTH, HEIGHT);
+        maze.algorithm(Algorithm::CellularAutomaton(Automaton { rule, steps: 50, density: 30 })).seed(5);
+        let grid = maze.generate();
+        let mut seen = HashSet::new();
+        let mut stack = vec![(0, 0)];
+        while let Some((x, y)) = stack.pop() {
+            if seen.insert((x, y)) {
+                stack.extend(PLANAR.iter().filter_map(|&dir| grid.passage(x, y, dir)));
+            }
+        }
+        assert_eq!(seen.len(), WIDTH * HEIGHT);
+        let parsed = MazeText::parse(&maze.text().to_string()).unwrap();
+        assert_eq!(parsed.to_string(), maze.text().to_string());
+        match parsed.algorithm {
+            Some(Algorithm::CellularAutomaton(automaton)) => assert_eq!(automaton.rule, rule),
+            _ => panic!("The algorithm wasn't parsed")
+        }
+    }
+}
+
+#[test]
+fn origin_shift_is_perfect() {
+    for &boundary in &[Boundary::Bounded, Boundary::Torus, Boundary::Moebius] {
+        let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, boundary);
+        algorithms::carve(&mut grid, &mut rand::thread_rng(), Algorithm::OriginShift(5), Weights::uniform());
+        assert_perfect(&grid);
+    }
+}
+
+#[test]
+fn origin_shift_steps() {
+    use rand::{SeedableRng, StdRng};
+    let grid = Maze::new(WIDTH / 4, HEIGHT / 4).seed(9).generate();
+    let entries = solver::entry_points(&grid);
+    let mut shift = OriginShift::new(grid.clone(), StdRng::from_seed(&[9][..]));
+    for _ in 0 .. 100 {
+        let origin = shift.origin();
+        shift.step(1);
+        // The old origin leads to the new one
+        assert!(PLANAR.iter().any(|&dir| shift.grid().passage(origin.0, origin.1, dir) == Some(shift.origin())));
+        assert_perfect(shift.grid());
+    }
+    assert_eq!(solver::entry_points(shift.grid()), entries);
+    assert!(MazeText::new(shift.grid()).to_string() != MazeText::new(&grid).to_string());
+}
+
+#[test]
+#[should_panic]
+fn origin_shift_fail_loops() {
+    let _ = OriginShift::new(Grid::new(WIDTH, HEIGHT, false), rand::thread_rng());
+}
+
 #[test]
 fn create_growing_tree() {
     let selections = [CellSelection::Newest, 
@@ -70,6 +232,263 @@ fn create_growing_tree() {
     }
 }
 
+#[test]
+fn growing_tree_selections_saved() {
+    let selections = [CellSelection::Middle,
+        CellSelection::FarthestRandom(30),
+        CellSelection::Mix { newest: 2, oldest: 1, random: 1 }];
+    for &selection in selections.iter() {
+        let mut maze = Maze::new(WIDTH, HEIGHT);
+        maze.algorithm(Algorithm::GrowingTree(selection));
+        let _ = maze.render(RENDER_SCALE);
+        // The selection is read back from the saved maze
+        let algorithm = MazeText::parse(&maze.text().to_string()).unwrap().algorithm;
+        assert_eq!(format!("{:?}", algorithm), format!("{:?}", Some(Algorithm::GrowingTree(selection))));
+    }
+}
+
+#[test]
+#[should_panic]
+fn growing_tree_fail_percentage() {
+    let _ = Maze::new(WIDTH, HEIGHT)
+        .algorithm(Algorithm::GrowingTree(CellSelection::NewestRandom(101)))
+        .generate();
+}
+
+#[test]
+fn growing_tree_farthest_levels() {
+    let layered = Maze::new(WIDTH / 4, HEIGHT / 4)
+        .levels(3)
+        .algorithm(Algorithm::GrowingTree(CellSelection::FarthestRandom(50)))
+        .generate_layers();
+    assert!((0 .. layered.size()).all(|i| !layered.is_closed(layered.position(i))));
+}
+
+#[test]
+fn growing_tree_selection_builtin() {
+    // A built-in selection carves the same maze through Maze::selection, every time it's generated
+    let grid = Maze::new(WIDTH, HEIGHT)
+        .algorithm(Algorithm::GrowingTree(CellSelection::FarthestRandom(50)))
+        .seed(4)
+        .generate();
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.selection(Either { first: Farthest::new(), second: Random, percent: 50 }).seed(4);
+    for _ in 0 .. 2 {
+        let other = maze.generate();
+        assert!((0 .. WIDTH).all(|x| (0 .. HEIGHT).all(|y| {
+            [Direction::South, Direction::East].iter().all(|&dir| grid.has_wall(x, y, dir) == other.has_wall(x, y, dir))
+        })));
+    }
+}
+
+// Always the second newest cell, when there's one
+struct SecondNewest;
+
+impl Selection for SecondNewest {
+    fn select(&mut self, cells: &[(usize, usize, usize)], _: &mut StdRng) -> usize {
+        cells.len().saturating_sub(2)
+    }
+}
+
+// The cell closest to the west edge, or a random one now and then
+struct Westmost {
+    picks: usize
+}
+
+impl Selection for Westmost {
+    fn select(&mut self, cells: &[(usize, usize, usize)], rng: &mut StdRng) -> usize {
+        self.picks += 1;
+        if rng.gen_weighted_bool(4) {
+            return rng.gen_range(0, cells.len());
+        }
+        (0 .. cells.len()).min_by_key(|&i| cells[i].0).unwrap()
+    }
+}
+
+#[test]
+fn custom_selection() {
+    for &boundary in &[Boundary::Bounded, Boundary::Torus, Boundary::Moebius] {
+        assert_perfect(&Maze::new(30, 20).boundary(boundary).selection(SecondNewest).generate());
+    }
+}
+
+#[test]
+fn custom_selection_seed() {
+    let mut maze = Maze::new(30, 20);
+    maze.selection(Westmost { picks: 0 }).seed(3);
+    let text = maze.text();
+    // A selection of your own can't be read back
+    assert!(text.algorithm.is_none());
+    assert_eq!(text.to_string(), maze.text().to_string());
+    assert_perfect(&maze.generate());
+}
+
+#[test]
+fn custom_selection_levels() {
+    let layered = Maze::new(6, 5).levels(3).selection(SecondNewest).generate_layers();
+    for grid in &layered.levels {
+        for x in 0 .. grid.width {
+            for y in 0 .. grid.height {
+                assert!(!grid.cell(x, y).is_closed());
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn custom_selection_fail_tiles() {
+    let _ = Maze::new(30, 20).selection(SecondNewest).tiles(10, 10).generate();
+}
+
+// A binary tree of its own, that carves south or east
+struct SouthEast;
+
+impl Generator for SouthEast {
+    fn carve(&mut self, grid: &mut Grid, rng: &mut StdRng) {
+        for x in 0 .. grid.width {
+            for y in 0 .. grid.height {
+                let dirs: Vec<Direction> = [Direction::South, Direction::East].iter().cloned()
+                    .filter(|&dir| grid.cell_at(x, y, dir).is_some())
+                    .collect();
+                if let Some(&dir) = rng.choose(&dirs) {
+                    grid.open(x, y, dir);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn create_custom_generator() {
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.generator(SouthEast).seed(5);
+    let grid = maze.generate();
+    assert_perfect(&grid);
+    assert_eq!(MazeText::new(&grid).to_string(), MazeText::new(&maze.generate()).to_string());
+    assert!(maze.text().algorithm.is_none());
+    // The algorithm takes over again
+    maze.algorithm(Algorithm::KruskalsAlgorithm);
+    assert!(maze.text().algorithm.is_some());
+}
+
+#[test]
+#[should_panic]
+fn custom_generator_fail_levels() {
+    let _ = Maze::new(WIDTH, HEIGHT).generator(SouthEast).levels(2).generate_layers();
+}
+
+#[test]
+fn count_solutions() {
+    // The ways across a 3x3 room from one corner to the other
+    let open = Grid::new(3, 3, false);
+    assert_eq!(solver::count_solutions(&open, (0, 0), (2, 2), 100), 12);
+    assert_eq!(solver::count_solutions(&open, (0, 0), (2, 2), 5), 5);
+    assert_eq!(solver::count_solutions(&open, (1, 1), (1, 1), 100), 1);
+    let mut grid = Maze::new(WIDTH, HEIGHT).algorithm(Algorithm::KruskalsAlgorithm).generate();
+    assert_eq!(solver::count_solutions(&grid, (0, 0), (WIDTH - 1, HEIGHT - 1), 100), 1);
+    grid.close(0, 0, Direction::East);
+    grid.close(0, 0, Direction::South);
+    assert_eq!(solver::count_solutions(&grid, (0, 0), (WIDTH - 1, HEIGHT - 1), 100), 0);
+}
+
+#[test]
+fn enumerate_solutions() {
+    let open = Grid::new(3, 3, false);
+    let paths = solver::solutions(&open, (0, 0), (2, 2), 100);
+    assert_eq!(paths.len(), 12);
+    let shortest = solver::shortest_paths(&open, (0, 0), (2, 2), 100);
+    assert_eq!(shortest.len(), 12);
+    assert!(shortest.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+    assert_eq!(shortest[0].len(), 5);
+    for path in paths.iter().chain(&shortest) {
+        assert_eq!((path[0], path[path.len() - 1]), ((0, 0), (2, 2)));
+        assert_eq!(path.iter().collect::<HashSet<_>>().len(), path.len());
+        let passage = |a: (usize, usize), b| PLANAR.iter().any(|&dir| open.passage(a.0, a.1, dir) == Some(b));
+        assert!(path.windows(2).all(|step| passage(step[0], step[1])));
+    }
+    assert_eq!(shortest.iter().collect::<HashSet<_>>().len(), 12);
+    assert_eq!(solver::articulation_cells(&open, (0, 0), (2, 2)), Some(Vec::new()));
+    let grid = Maze::new(WIDTH, HEIGHT).loops(Loops::Solutions(3)).seed(8).generate();
+    let points = solver::entry_points(&grid);
+    let (from, to) = (points[0], points[1]);
+    let paths = solver::solutions(&grid, from, to, 100);
+    assert_eq!(paths.len(), solver::count_solutions(&grid, from, to, 100));
+    let shortest = solver::shortest_paths(&grid, from, to, 2);
+    assert_eq!(shortest[0].len(), solver::shortest_path(&grid, from, to).unwrap().len());
+    let cells = solver::articulation_cells(&grid, from, to).unwrap();
+    assert!(paths.iter().all(|path| cells.iter().all(|cell| path.contains(cell))));
+    // In a perfect maze every cell of the solution is a cut
+    let perfect = Maze::new(WIDTH, HEIGHT).seed(8).generate();
+    let (from, to) = ((0, 0), (WIDTH - 1, HEIGHT - 1));
+    let path = solver::shortest_path(&perfect, from, to).unwrap();
+    assert_eq!(solver::articulation_cells(&perfect, from, to).unwrap(), path[1 .. path.len() - 1].to_vec());
+    assert_eq!(solver::solutions(&perfect, from, to, 100), vec![path]);
+}
+
+// The walk goes from one cell to the other through passages, and so does its path
+fn assert_walk(grid: &Grid, walk: &solver::Walk, from: (usize, usize), to: (usize, usize)) {
+    let passage = |a: (usize, usize), b| PLANAR.iter().any(|&dir| grid.passage(a.0, a.1, dir) == Some(b));
+    for steps in &[&walk.steps, &walk.path] {
+        assert_eq!((steps[0], steps[steps.len() - 1]), (from, to));
+        assert!(steps.windows(2).all(|step| passage(step[0], step[1])));
+    }
+    assert_eq!(walk.path.iter().collect::<HashSet<_>>().len(), walk.path.len());
+}
+
+#[test]
+fn human_solvers() {
+    for &boundary in &[Boundary::Bounded, Boundary::Moebius] {
+        let grid = Maze::new(WIDTH, HEIGHT).boundary(boundary).generate();
+        let points = solver::entry_points(&grid);
+        let (from, to) = (points[0], points[1]);
+        let path = solver::shortest_path(&grid, from, to).unwrap();
+        let mut walks = vec![solver::wall_follower(&grid, from, to, solver::Hand::Left).unwrap(),
+            solver::wall_follower(&grid, from, to, solver::Hand::Right).unwrap(),
+            solver::tremaux(&grid, from, to).unwrap()];
+        if boundary == Boundary::Bounded {
+            walks.push(solver::pledge(&grid, from, to, Direction::East).unwrap());
+        }
+        for walk in &walks {
+            assert_walk(&grid, walk, from, to);
+            assert_eq!(walk.path, path);
+            assert!(walk.steps.len() >= path.len());
+        }
+        // Every cell off the solution of a perfect maze is a dead end
+        let filled = solver::dead_end_filling(&grid, from, to).unwrap();
+        assert_eq!(filled.path, path);
+        assert_eq!(filled.steps.len() + path.len(), WIDTH * HEIGHT);
+    }
+    // A wall follower can miss an end in the middle, Trémaux's algorithm can't
+    let grid = Maze::new(WIDTH, HEIGHT).loops(Loops::Count(50)).generate();
+    let (from, to) = (solver::entry_points(&grid)[0], (WIDTH / 2, HEIGHT / 2));
+    assert_walk(&grid, &solver::tremaux(&grid, from, to).unwrap(), from, to);
+    if let Some(walk) = solver::wall_follower(&grid, from, to, solver::Hand::Left) {
+        assert_walk(&grid, &walk, from, to);
+    }
+}
+
+#[test]
+fn insert_loops() {
+    let passages = |grid: &Grid| (0 .. grid.width).flat_map(|x| (0 .. grid.height).map(move |y| (x, y)))
+        .map(|(x, y)| [Direction::East, Direction::South].iter()
+            .filter(|&&dir| grid.cell_at(x, y, dir).is_some() && !grid.has_wall(x, y, dir)).count())
+        .sum::<usize>();
+    let grid = Maze::new(WIDTH, HEIGHT).loops(Loops::Count(10)).seed(4).generate();
+    assert_eq!(passages(&grid), WIDTH * HEIGHT - 1 + 10);
+    for &algorithm in &[Algorithm::RecursiveBacktracking, Algorithm::BinaryTree(Bias::Northeast)] {
+        let grid = Maze::new(WIDTH, HEIGHT).algorithm(algorithm).loops(Loops::Solutions(6)).generate();
+        let points = solver::entry_points(&grid);
+        assert!(solver::count_solutions(&grid, points[0], points[1], 100) >= 6);
+    }
+}
+
+#[test]
+#[should_panic]
+fn insert_loops_fail_torus() {
+    let _ = Maze::new(WIDTH, HEIGHT).loops(Loops::Solutions(2)).boundary(Boundary::Torus);
+}
+
 #[test]
 fn create_binary_tree() {
     let biases = [Bias::Northeast, 
@@ -93,9 +512,558 @@ fn create_sidewinder_algorithm() {
     }    
 }
 
+#[test]
+fn sidewinder_algorithm_ends_runs() {
+    // A run going past the last cell of a line never opened up, cutting it off
+    for &scan in &[Scan::Horizontal, Scan::Vertical] {
+        for seed in 0 .. 10 {
+            let grid = Maze::new(3, 3).algorithm(Algorithm::SidewinderAlgorithm(scan)).seed(seed).generate();
+            assert_perfect(&grid);
+        }
+    }
+}
+
 #[test]
 fn create_hunt_kill_algorithm() {
     let _ = Maze::new(WIDTH, HEIGHT)
         .algorithm(Algorithm::HuntKillAlgorithm)
         .render(RENDER_SCALE);
 }
+
+#[test]
+fn hunt_kill_orders_are_perfect() {
+    for &order in &[Hunt::Columns, Hunt::Rows, Hunt::Random, Hunt::Serpentine] {
+        for &boundary in &[Boundary::Bounded, Boundary::Moebius] {
+            let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, boundary);
+            let algorithm = Algorithm::HuntKillAlgorithmWith(order);
+            algorithms::carve(&mut grid, &mut rand::thread_rng(), algorithm, Weights::uniform());
+            assert_perfect(&grid);
+        }
+    }
+}
+
+#[test]
+fn render_native_scale() {
+    let base = Maze::new(WIDTH, HEIGHT).render(RENDER_SCALE);
+    let (base_width, base_height) = base.image().unwrap().dimensions();
+    let scaled = Maze::new(WIDTH, HEIGHT).render(2.0);
+    let img = scaled.image().unwrap().to_luma();
+    assert_eq!(img.dimensions(), (base_width * 2, base_height * 2));
+    // No resampling, so only black and white pixels
+    assert!(img.pixels().all(|p| p.data[0] == 0 || p.data[0] == 255));
+}
+
+#[test]
+fn create_weave_kruskals_algorithm() {
+    let _ = Maze::new(WIDTH, HEIGHT)
+        .algorithm(Algorithm::WeaveKruskalsAlgorithm(80))
+        .render(RENDER_SCALE);
+}
+
+#[test]
+fn weave_kruskals_is_perfect() {
+    let mut rng = rand::thread_rng();
+    let mut grid = Grid::new(WIDTH, HEIGHT, true);
+    weave_kruskals_algorithm::carve(&mut grid, &mut rng, 100);
+    assert!(grid.has_crossings());
+    // Crossings are passed through, so the regular cells must form a spanning tree
+    let dirs = [Direction::North, Direction::South, Direction::East, Direction::West];
+    let mut cells = 0;
+    let mut passages = 0;
+    let mut seen = HashSet::new();
+    let mut stack = vec![(0, 0)];
+    for x in 0 .. WIDTH {
+        for y in 0 .. HEIGHT {
+            if grid.crossing(x, y).is_none() {
+                cells += 1;
+                passages += dirs.iter().filter(|&&d| grid.passage(x, y, d).is_some()).count();
+            }
+        }
+    }
+    while let Some((x, y)) = stack.pop() {
+        if seen.insert((x, y)) {
+            stack.extend(dirs.iter().filter_map(|&d| grid.passage(x, y, d)));
+        }
+    }
+    assert_eq!(seen.len(), cells);
+    assert_eq!(passages / 2, cells - 1);
+}
+
+// The regular cells of the grid must form a spanning tree, crossings are passed through
+fn assert_perfect(grid: &Grid) {
+    let dirs = [Direction::North, Direction::South, Direction::East, Direction::West];
+    let mut cells = Vec::new();
+    let mut passages = 0;
+    for x in 0 .. grid.width {
+        for y in 0 .. grid.height {
+            if grid.crossing(x, y).is_none() {
+                cells.push((x, y));
+                passages += dirs.iter().filter(|&&d| grid.passage(x, y, d).is_some()).count();
+            }
+        }
+    }
+    let mut seen = HashSet::new();
+    let mut stack = vec![cells[0]];
+    while let Some((x, y)) = stack.pop() {
+        if seen.insert((x, y)) {
+            stack.extend(dirs.iter().filter_map(|&d| grid.passage(x, y, d)));
+        }
+    }
+    assert_eq!(seen.len(), cells.len());
+    assert_eq!(passages / 2, cells.len() - 1);
+}
+
+#[test]
+fn tiled_is_perfect() {
+    let algorithms = [Algorithm::BinaryTree(Bias::Southwest),
+        Algorithm::EllersAlgorithm(Scan::Vertical),
+        Algorithm::RecursiveBacktracking,
+        Algorithm::GrowingTree(CellSelection::NewestRandom(50)),
+        Algorithm::SidewinderAlgorithm(Scan::Horizontal),
+        Algorithm::RecursiveDivision,
+        Algorithm::WeaveKruskalsAlgorithm(50)];
+    for &algorithm in algorithms.iter() {
+        for &boundary in &[Boundary::Bounded, Boundary::Torus, Boundary::Moebius] {
+            let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, boundary);
+            tiled::carve(&mut grid, &mut rand::thread_rng(), algorithm, Weights::uniform(), 16, 12);
+            assert_perfect(&grid);
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn tiled_fail_levels() {
+    let _ = Maze::new(WIDTH, HEIGHT).levels(2).tiles(10, 10).generate_layers();
+}
+
+#[test]
+fn tiled_seed() {
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.algorithm(Algorithm::KruskalsAlgorithm).tiles(10, 10).seed(8);
+    assert_eq!(maze.text().to_string(), maze.text().to_string());
+}
+
+#[test]
+fn weights_bias_passages() {
+    // The number of east-west and north-south passages
+    let count = |grid: &Grid| {
+        let mut passages = (0, 0);
+        for x in 0 .. grid.width {
+            for y in 0 .. grid.height {
+                passages.0 += (x + 1 < grid.width && !grid.has_wall(x, y, Direction::East)) as usize;
+                passages.1 += (y + 1 < grid.height && !grid.has_wall(x, y, Direction::South)) as usize;
+            }
+        }
+        passages
+    };
+    let algorithms = [Algorithm::RecursiveBacktracking,
+        Algorithm::HuntKillAlgorithm,
+        Algorithm::PrimsAlgorithm,
+        Algorithm::PrimsAlgorithmWith(Prims::Edges),
+        Algorithm::GrowingTree(CellSelection::NewestRandom(50)),
+        Algorithm::RecursiveDivision];
+    for &algorithm in algorithms.iter() {
+        let wide = count(&Maze::new(WIDTH, HEIGHT).algorithm(algorithm).weights(Weights::axes(10, 1)).generate());
+        let tall = count(&Maze::new(WIDTH, HEIGHT).algorithm(algorithm).weights(Weights::axes(1, 10)).generate());
+        assert!(wide.0 > tall.0 && wide.1 < tall.1, "{:?}: {:?} against {:?}", algorithm, wide, tall);
+    }
+}
+
+#[test]
+fn weights_zero_stays_perfect() {
+    let weights = Weights { north: 0, south: 1, east: 0, west: 5, stairs: 1 };
+    let algorithms = [Algorithm::RecursiveBacktracking, Algorithm::HuntKillAlgorithm,
+        Algorithm::PrimsAlgorithm, Algorithm::PrimsAlgorithmWith(Prims::Edges)];
+    for &algorithm in algorithms.iter() {
+        let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, Boundary::Torus);
+        algorithms::carve(&mut grid, &mut rand::thread_rng(), algorithm, weights);
+        assert_perfect(&grid);
+    }
+}
+
+#[test]
+fn regions_are_perfect() {
+    // A circle, which the rectangle only algorithms cut into pieces
+    let circle = (0 .. WIDTH).flat_map(|x| (0 .. HEIGHT).map(move |y| (x, y)))
+        .filter(|&(x, y)| (x as i32 - 70).pow(2) + (y as i32 - 25).pow(2) < 400);
+    let rings = (0 .. WIDTH / 2).flat_map(|x| (0 .. HEIGHT).map(move |y| (x, y)))
+        .filter(|&(x, y)| (x / 5 + y / 5) % 2 == 0);
+    let algorithms = [Algorithm::SidewinderAlgorithm(Scan::Vertical),
+        Algorithm::KruskalsAlgorithm,
+        Algorithm::BlobbyDivision(1)];
+    for &algorithm in algorithms.iter() {
+        for &boundary in &[Boundary::Bounded, Boundary::Torus] {
+            let grid = Maze::new(WIDTH, HEIGHT)
+                .boundary(boundary)
+                .region(Region::rect(0, 0, WIDTH / 2, HEIGHT, Algorithm::BinaryTree(Bias::Northeast)))
+                .region(Region::rect(WIDTH / 2, 0, WIDTH / 2, HEIGHT / 2, Algorithm::WeaveKruskalsAlgorithm(100)))
+                .region(Region::masked(circle.clone(), algorithm))
+                .region(Region::masked(rings.clone(), Algorithm::HuntKillAlgorithm))
+                .generate();
+            assert!(grid.has_crossings());
+            assert_perfect(&grid);
+        }
+    }
+}
+
+#[test]
+fn regions_doors() {
+    let grid = Maze::new(WIDTH, HEIGHT)
+        .region(Region::rect(0, 0, WIDTH / 2, HEIGHT, Algorithm::PrimsAlgorithm))
+        .doors(5)
+        .generate();
+    let mut passages = 0;
+    for x in 0 .. WIDTH {
+        for y in 0 .. HEIGHT {
+            passages += (x + 1 < WIDTH && !grid.has_wall(x, y, Direction::East)) as usize;
+            passages += (y + 1 < HEIGHT && !grid.has_wall(x, y, Direction::South)) as usize;
+        }
+    }
+    let doors = (0 .. HEIGHT).filter(|&y| !grid.has_wall(WIDTH / 2 - 1, y, Direction::East)).count();
+    assert_eq!(doors, 5);
+    // Every door but one adds a loop
+    assert_eq!(passages, WIDTH * HEIGHT - 1 + 4);
+}
+
+#[test]
+fn create_layered() {
+    let algorithms = [Algorithm::RecursiveBacktracking,
+        Algorithm::HuntKillAlgorithm,
+        Algorithm::PrimsAlgorithm,
+        Algorithm::GrowingTree(CellSelection::NewestRandom(50)),
+        Algorithm::KruskalsAlgorithm,
+        Algorithm::BlobbyDivision(4)];
+    for &algorithm in algorithms.iter() {
+        let _ = Maze::new(WIDTH / 4, HEIGHT / 4)
+            .levels(3)
+            .algorithm(algorithm)
+            .render(RENDER_SCALE);
+    }
+}
+
+#[test]
+#[should_panic]
+fn create_layered_fail_algorithm() {
+    let _ = Maze::new(WIDTH, HEIGHT)
+        .levels(2)
+        .algorithm(Algorithm::BinaryTree(Bias::Northeast))
+        .render(RENDER_SCALE);
+}
+
+#[test]
+fn layered_kruskals_is_perfect() {
+    let mut rng = rand::thread_rng();
+    let (width, height, depth) = (WIDTH / 4, HEIGHT / 4, 3);
+    let mut grid = LayeredGrid::new(width, height, depth, true);
+    kruskals_algorithm::carve(&mut grid, &mut rng);
+    let mut passages = 0;
+    let mut stairs = 0;
+    let mut seen = HashSet::new();
+    let mut stack = vec![(0, 0, 0)];
+    while let Some((x, y, z)) = stack.pop() {
+        if seen.insert((x, y, z)) {
+            for &dir in SPATIAL.iter() {
+                if !grid.levels[z].has_wall(x, y, dir) {
+                    passages += 1;
+                    if let Direction::Up = dir {
+                        stairs += 1;
+                    }
+                    stack.push(grid.cell_at(x, y, z, dir).unwrap());
+                }
+            }
+        }
+    }
+    assert_eq!(seen.len(), width * height * depth);
+    assert_eq!(passages / 2, seen.len() - 1);
+    assert!(stairs >= depth - 1);
+}
+
+#[test]
+fn create_boundaries() {
+    let boundaries = [Boundary::Bounded,
+        Boundary::Cylinder,
+        Boundary::Torus,
+        Boundary::Moebius];
+    for &boundary in boundaries.iter() {
+        let _ = Maze::new(WIDTH, HEIGHT)
+            .boundary(boundary)
+            .algorithm(Algorithm::GrowingTree(CellSelection::NewestRandom(50)))
+            .render(RENDER_SCALE);
+    }
+}
+
+#[test]
+fn boundary_neighbors() {
+    let grid = Grid::with_boundary(WIDTH, HEIGHT, true, Boundary::Cylinder);
+    assert_eq!(grid.cell_at(WIDTH - 1, 3, Direction::East), Some((0, 3)));
+    assert_eq!(grid.cell_at(3, 0, Direction::North), None);
+    let grid = Grid::with_boundary(WIDTH, HEIGHT, true, Boundary::Torus);
+    assert_eq!(grid.cell_at(3, 0, Direction::North), Some((3, HEIGHT - 1)));
+    let grid = Grid::with_boundary(WIDTH, HEIGHT, true, Boundary::Moebius);
+    assert_eq!(grid.cell_at(WIDTH - 1, 3, Direction::East), Some((0, HEIGHT - 4)));
+    assert_eq!(grid.cell_at(0, HEIGHT - 4, Direction::West), Some((WIDTH - 1, 3)));
+}
+
+#[test]
+fn lines_wrap_around() {
+    // The lines of Eller's and sidewinder run around the edges they wrap into
+    let wrapped = |grid: &Grid, dir| match dir {
+        Direction::East => (0 .. grid.height).any(|y| !grid.has_wall(grid.width - 1, y, dir)),
+        _               => (0 .. grid.width).any(|x| !grid.has_wall(x, grid.height - 1, dir))
+    };
+    let cases = [(Scan::Horizontal, Boundary::Cylinder, Direction::East),
+        (Scan::Horizontal, Boundary::Torus, Direction::East),
+        (Scan::Vertical, Boundary::Torus, Direction::South)];
+    for &(scan, boundary, dir) in cases.iter() {
+        for &algorithm in &[Algorithm::EllersAlgorithm(scan), Algorithm::SidewinderAlgorithm(scan)] {
+            let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, boundary);
+            algorithms::carve(&mut grid, &mut rand::thread_rng(), algorithm, Weights::uniform());
+            assert_perfect(&grid);
+            assert!(wrapped(&grid, dir));
+        }
+    }
+    // Upside down, the lines can't go around
+    let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, Boundary::Moebius);
+    algorithms::carve(&mut grid, &mut rand::thread_rng(), Algorithm::EllersAlgorithm(Scan::Horizontal),
+        Weights::uniform());
+    assert_perfect(&grid);
+    assert!(!wrapped(&grid, Direction::East));
+}
+
+#[test]
+fn grid_shared_walls() {
+    let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, Boundary::Moebius);
+    grid.open(WIDTH - 1, 3, Direction::East);
+    assert!(!grid.has_wall(0, HEIGHT - 4, Direction::West));
+    grid.open(5, 0, Direction::North);
+    grid.open(5, 0, Direction::South);
+    assert!(!grid.has_wall(5, 1, Direction::North));
+    assert!(grid.has_wall(5, HEIGHT - 1, Direction::South));
+    grid.close(5, 1, Direction::North);
+    assert!(grid.has_wall(5, 0, Direction::South));
+    // Stairs stay closed until opened
+    assert!(grid.cell(7, 7).get(Direction::Up));
+    grid.open(7, 7, Direction::Down);
+    assert!(!grid.cell(7, 7).get(Direction::Down));
+    assert!(grid.has_wall(7, 7, Direction::Up));
+}
+
+#[test]
+fn torus_recursive_backtracking_is_perfect() {
+    let mut rng = rand::thread_rng();
+    let mut grid = Grid::with_boundary(WIDTH, HEIGHT, true, Boundary::Torus);
+    recursive_backtracking::carve(&mut grid, &mut rng, Weights::uniform());
+    choose_entry_points(&mut grid, &mut rng);
+    let dirs = [Direction::North, Direction::South, Direction::East, Direction::West];
+    let mut passages = 0;
+    let mut seen = HashSet::new();
+    let mut stack = vec![(0, 0)];
+    while let Some((x, y)) = stack.pop() {
+        if seen.insert((x, y)) {
+            for &dir in dirs.iter() {
+                // A torus has no border, so every open wall leads somewhere
+                if !grid.has_wall(x, y, dir) {
+                    passages += 1;
+                    stack.push(grid.cell_at(x, y, dir).unwrap());
+                }
+            }
+        }
+    }
+    assert_eq!(seen.len(), WIDTH * HEIGHT);
+    assert_eq!(passages / 2, seen.len() - 1);
+}
+
+#[test]
+fn text_round_trip() {
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.algorithm(Algorithm::WeaveKruskalsAlgorithm(50))
+        .boundary(Boundary::Cylinder)
+        .seed(42);
+    let text = maze.text().to_string();
+    let parsed = MazeText::parse(&text).unwrap();
+    assert_eq!(parsed.seed, Some(42));
+    assert!(parsed.grid.has_crossings());
+    assert_eq!(parsed.to_string(), text);
+    // The same seed generates the same maze
+    assert_eq!(maze.text().to_string(), text);
+}
+
+#[test]
+fn text_parse() {
+    let text = "maze 4 3\n\
+        # a hand made level\n\
+        algorithm growing-tree selection=newest-random:20\n\
+        +--+--+  +--+\n\
+        |           |\n\
+        +  +  +--+  +\n\
+        |   ==   |  |\n\
+        +--+  +--+  +\n\
+        |     |     |\n\
+        +  +--+--+--+\n";
+    let parsed = MazeText::parse(text).unwrap();
+    let grid = parsed.grid;
+    assert_eq!((grid.width, grid.height), (4, 3));
+    assert_eq!(grid.crossing(1, 1), Some(Crossing::Horizontal));
+    assert_eq!(grid.passage(0, 1, Direction::East), Some((2, 1)));
+    assert!(!grid.has_wall(2, 0, Direction::North));
+    assert!(grid.has_wall(3, 1, Direction::West));
+    match parsed.algorithm {
+        Some(Algorithm::GrowingTree(CellSelection::NewestRandom(20))) => {}
+        _ => panic!("The algorithm wasn't parsed")
+    }
+    assert!(MazeText::parse("maze 4 3\n+--+\n").is_err());
+    assert!(MazeText::parse("+--+\n|  |\n+--+\n").is_err());
+}
+
+#[test]
+fn algorithm_syntax() {
+    let algorithms = [Algorithm::BinaryTree(Bias::Southwest),
+        Algorithm::EllersAlgorithm(Scan::Vertical),
+        Algorithm::RecursiveBacktracking,
+        Algorithm::HuntKillAlgorithm,
+        Algorithm::HuntKillAlgorithmWith(Hunt::Serpentine),
+        Algorithm::PrimsAlgorithm,
+        Algorithm::PrimsAlgorithmWith(Prims::Edges),
+        Algorithm::GrowingTree(CellSelection::FarthestRandom(30)),
+        Algorithm::GrowingTree(CellSelection::Mix { newest: 1, oldest: 2, random: 3 }),
+        Algorithm::SidewinderAlgorithm(Scan::Horizontal),
+        Algorithm::KruskalsAlgorithm,
+        Algorithm::RecursiveDivision,
+        Algorithm::RecursiveDivisionWith(Division { room_size: 3, centering: 20, passages: 2 }),
+        Algorithm::BlobbyDivision(12),
+        Algorithm::CellularAutomaton(Automaton { rule: Rule::Mazectric, steps: 40, density: 25 }),
+        Algorithm::OriginShift(7),
+        Algorithm::WeaveKruskalsAlgorithm(80)];
+    for &algorithm in algorithms.iter() {
+        let parsed: Algorithm = algorithm.to_string().parse().unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", algorithm));
+    }
+    assert_eq!(Algorithm::GrowingTree(CellSelection::NewestRandom(50)).to_string(),
+        "growing-tree selection=newest-random:50");
+    // Left out settings take their default
+    let parsed: Algorithm = "recursive-division passages=3".parse().unwrap();
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", Algorithm::RecursiveDivisionWith(Division { passages: 3,
+        ..Division::default() })));
+    assert!("binary-tree scan=vertical".parse::<Algorithm>().is_err());
+    assert!("BinaryTree(Northeast)".parse::<Algorithm>().is_err());
+    // The short names of the command line are aliases
+    let parsed: Algorithm = "binary-tree bias=sw".parse().unwrap();
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", Algorithm::BinaryTree(Bias::Southwest)));
+    assert!("growing-tree selection=newest-random:101".parse::<Algorithm>().is_err());
+    assert!("growing-tree selection=mix:0,0,0".parse::<Algorithm>().is_err());
+    assert!("recursive-division rooms=0".parse::<Algorithm>().is_err());
+    assert_eq!("moebius".parse::<Boundary>().unwrap(), Boundary::Moebius);
+}
+
+#[test]
+fn ellers_lines_match_grid() {
+    use rand::{SeedableRng, StdRng};
+    let mut grid = Grid::new(WIDTH, HEIGHT, true);
+    ellers_algorithm::carve(&mut grid, &mut StdRng::from_seed(&[11][..]), Scan::Horizontal);
+    grid.open(0, 0, Direction::North);
+    grid.open(WIDTH - 1, HEIGHT - 1, Direction::South);
+    let text = MazeText::new(&grid).to_string();
+    let drawing = &text[text.find('+').unwrap() ..];
+    let lines = ellers_algorithm::EllersLines::new(WIDTH, Some(HEIGHT), StdRng::from_seed(&[11][..]));
+    let mut streamed = Vec::new();
+    MazeText::write_lines(&mut streamed, lines).unwrap();
+    assert_eq!(String::from_utf8(streamed).unwrap(), drawing);
+}
+
+#[test]
+fn ellers_lines_endless() {
+    let lines = ellers_algorithm::EllersLines::new(WIDTH, None, rand::thread_rng());
+    for line in lines.take(10 * HEIGHT) {
+        assert!(line.side[WIDTH - 1]);
+        assert!(line.next.iter().any(|&closed| !closed));
+    }
+}
+
+#[test]
+fn import_render() {
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.boundary(Boundary::Cylinder).seed(3);
+    let maze_render = maze.render(2.0);
+    let grid = MazeImport::new()
+        .scale(2.0)
+        .boundary(Boundary::Cylinder)
+        .import(maze_render.image().unwrap())
+        .unwrap();
+    assert_eq!(MazeText::new(&grid).to_string(), MazeText::new(&maze.generate()).to_string());
+}
+
+#[test]
+fn import_weave_render() {
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.algorithm(Algorithm::WeaveKruskalsAlgorithm(50)).seed(5);
+    let grid = MazeImport::new().import(maze.render(RENDER_SCALE).image().unwrap()).unwrap();
+    assert!(grid.has_crossings());
+    assert_eq!(MazeText::new(&grid).to_string(), MazeText::new(&maze.generate()).to_string());
+}
+
+#[test]
+fn import_wrong_geometry() {
+    let maze_render = Maze::new(WIDTH, HEIGHT).render(RENDER_SCALE);
+    assert!(MazeImport::new().scale(2.0).import(maze_render.image().unwrap()).is_err());
+}
+
+#[test]
+fn solve_entry_points() {
+    for &boundary in &[Boundary::Bounded, Boundary::Cylinder, Boundary::Moebius] {
+        let grid = Maze::new(WIDTH, HEIGHT).boundary(boundary).generate();
+        let points = solver::entry_points(&grid);
+        assert_eq!(points.len(), 2);
+        let path = solver::shortest_path(&grid, points[0], points[1]).unwrap();
+        assert_eq!((path[0], path[path.len() - 1]), (points[0], points[1]));
+    }
+    let torus = Maze::new(WIDTH, HEIGHT).boundary(Boundary::Torus).generate();
+    assert!(solver::entry_points(&torus).is_empty());
+}
+
+#[test]
+fn solve_shortest_path() {
+    let text = "maze 4 3\n\
+        +--+--+  +--+\n\
+        |           |\n\
+        +  +  +--+  +\n\
+        |   ==   |  |\n\
+        +--+  +--+  +\n\
+        |     |     |\n\
+        +  +--+--+--+\n";
+    let grid = MazeText::parse(text).unwrap().grid;
+    assert_eq!(solver::entry_points(&grid), vec![(0, 2), (2, 0)]);
+    // The path goes over the crossing at (1, 1)
+    let path = solver::shortest_path(&grid, (0, 2), (2, 0)).unwrap();
+    assert_eq!(path, vec![(0, 2), (1, 2), (1, 0), (2, 0)]);
+    let mut closed = grid.clone();
+    closed.close(1, 2, Direction::West);
+    assert_eq!(solver::shortest_path(&closed, (0, 2), (2, 0)), None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip() {
+    use serde_json;
+    let mut maze = Maze::new(WIDTH, HEIGHT);
+    maze.algorithm(Algorithm::WeaveKruskalsAlgorithm(50)).seed(7);
+    let grid = maze.generate();
+    let json = serde_json::to_string(&grid).unwrap();
+    let parsed: Grid = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.boundary(), grid.boundary());
+    assert_eq!(MazeText::new(&parsed).to_string(), MazeText::new(&grid).to_string());
+    // The walls are checked against the size
+    let json = json.replacen(&format!("\"width\":{}", WIDTH), "\"width\":30", 1);
+    assert!(serde_json::from_str::<Grid>(&json).is_err());
+    // The walls are required
+    let json = r#"{"width":2,"height":1,"boundary":"Bounded","crossings":[]}"#;
+    assert!(serde_json::from_str::<Grid>(json).is_err());
+    let layers = Maze::new(4, 3).levels(3).generate_layers();
+    let parsed: LayeredGrid = serde_json::from_str(&serde_json::to_string(&layers).unwrap()).unwrap();
+    assert_eq!(serde_json::to_string(&parsed).unwrap(), serde_json::to_string(&layers).unwrap());
+
+    let algorithm = Algorithm::GrowingTree(CellSelection::NewestRandom(20));
+    let json = serde_json::to_string(&algorithm).unwrap();
+    let parsed: Algorithm = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", algorithm));
+}
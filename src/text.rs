@@ -0,0 +1,527 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use algorithms::{Algorithm, Automaton, Bias, CellSelection, Division, Hunt, Prims, Rule, Scan};
+use algorithms::ellers_algorithm::Line;
+use grid::{Boundary, Crossing, Direction, Grid};
+
+// The plain text save format. A header of "key value" lines comes first, then the
+// maze is drawn with '+' at the corners, "--" for closed north and south walls and
+// '|' for closed east and west walls:
+//
+//   maze 4 3
+//   boundary bounded
+//   algorithm weave-kruskals density=50
+//   seed 42
+//   +--+--+  +--+
+//   |           |
+//   +  +  +--+  +
+//   |   ==   |  |
+//   +--+  +--+  +
+//   |     |     |
+//   +  +--+--+--+
+//
+// The "maze <width> <height>" line is required and must come first. The boundary
+// (bounded, cylinder, torus or moebius) defaults to bounded, while the algorithm and
+// seed lines are optional. Lines starting with '#' in the header are comments.
+// The algorithm is written as its name followed by its settings as key=value:
+//
+//   binary-tree bias=northeast|northwest|southeast|southwest (or ne, nw, se, sw)
+//   ellers scan=horizontal|vertical (or h, v)
+//   recursive-backtracking
+//   hunt-kill hunt=columns|rows|random|serpentine
+//   prims cost=none|cells|edges
+//   growing-tree selection=newest|oldest|random|middle|newest-oldest:N|newest-random:N|
+//                          oldest-random:N|farthest-random:N|mix:N,O,R
+//   sidewinder scan=horizontal|vertical (or h, v)
+//   kruskals
+//   recursive-division rooms=N centering=N passages=N
+//   blobby-division cells=N
+//   cellular-automaton rule=maze|mazectric steps=N density=N
+//   origin-shift steps=N
+//   weave-kruskals density=N
+//
+// A setting left out takes its default. The percentages of growing-tree are at most
+// 100, and rooms, passages and cells are at least 1.
+// Openings in the outer walls are the entry and exit points. The inside of a cell is
+// blank, or "==" and "||" for a crossing whose horizontal or vertical passage goes under.
+pub struct MazeText {
+    pub grid: Grid,
+    pub algorithm: Option<Algorithm>,
+    pub seed: Option<u64>
+}
+
+impl MazeText {
+    pub fn new(grid: &Grid) -> MazeText {
+        MazeText {
+            grid: grid.clone(),
+            algorithm: None,
+            seed: None
+        }
+    }
+
+    pub fn parse(text: &str) -> io::Result<MazeText> {
+        let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line)).peekable();
+        // Header
+        let mut size = None;
+        let mut boundary = Boundary::Bounded;
+        let mut algorithm = None;
+        let mut seed = None;
+        while let Some(&(n, line)) = lines.peek() {
+            if line.starts_with('+') {
+                break;
+            }
+            lines.next();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() || words[0].starts_with('#') {
+                continue;
+            }
+            match (size, &words[..]) {
+                (None, &["maze", width, height]) => {
+                    match (width.parse(), height.parse()) {
+                        (Ok(width), Ok(height)) if width > 0 && height > 0 => size = Some((width, height)),
+                        _ => return Err(invalid(n, "the maze width, height must be greater than 0"))
+                    }
+                }
+                (None, _) => return Err(invalid(n, "expected 'maze <width> <height>'")),
+                (Some(_), &["boundary", name]) => {
+                    boundary = parse_boundary(name).ok_or_else(|| invalid(n, "unknown boundary"))?;
+                }
+                // The settings of an algorithm may have spaces in them
+                (Some(_), &["algorithm", _, ..]) => {
+                    algorithm = Some(parse_algorithm(&words[1 ..].join(" ")).map_err(|msg| invalid(n, &msg))?);
+                }
+                (Some(_), &["seed", value]) => {
+                    seed = Some(value.parse().map_err(|_| invalid(n, "the seed must be a number"))?);
+                }
+                _ => return Err(invalid(n, "unknown header line"))
+            }
+        }
+        let (width, height) = size.ok_or_else(|| invalid(0, "the 'maze <width> <height>' line is missing"))?;
+        // Walls. Every cell starts closed and the blanks in the drawing are opened.
+        let mut grid = Grid::with_boundary(width, height, true, boundary);
+        let mut crossings = Vec::new();
+        let line_len = 3 * width + 1;
+        for row in 0 .. 2 * height + 1 {
+            let (n, line) = lines.next().ok_or_else(|| invalid(0, "the drawing of the maze is cut short"))?;
+            let mut chars: Vec<char> = line.chars().collect();
+            // Editors may strip the blanks at the end of a line
+            if chars.len() > line_len {
+                return Err(invalid(n, "the line is longer than the maze"));
+            }
+            chars.resize(line_len, ' ');
+            let y = row / 2;
+            if row % 2 == 0 {
+                // The north walls of a row, or the south walls of the last one
+                let (cy, dir) = if y == height { (y - 1, Direction::South) } else { (y, Direction::North) };
+                for x in 0 .. width {
+                    corner(n, chars[3 * x])?;
+                    match (chars[3 * x + 1], chars[3 * x + 2]) {
+                        ('-', '-') => {}
+                        (' ', ' ') => grid.open(x, cy, dir),
+                        _          => return Err(invalid(n, "expected '--' or '  ' between corners"))
+                    }
+                }
+                corner(n, chars[3 * width])?;
+            } else {
+                for x in 0 .. width {
+                    if side_open(n, chars[3 * x])? {
+                        grid.open(x, y, Direction::West);
+                    }
+                    match (chars[3 * x + 1], chars[3 * x + 2]) {
+                        (' ', ' ') => {}
+                        ('=', '=') => crossings.push((x, y, Crossing::Horizontal)),
+                        ('|', '|') => crossings.push((x, y, Crossing::Vertical)),
+                        _          => return Err(invalid(n, "expected '  ', '==' or '||' inside a cell"))
+                    }
+                }
+                if side_open(n, chars[3 * width])? {
+                    grid.open(width - 1, y, Direction::East);
+                }
+            }
+        }
+        if let Some((n, _)) = lines.find(|&(_, line)| !line.trim().is_empty()) {
+            return Err(invalid(n, "unexpected line after the drawing of the maze"));
+        }
+        for (x, y, under) in crossings {
+            grid.add_crossing(x, y, under);
+        }
+        Ok(MazeText { grid, algorithm, seed })
+    }
+
+    pub fn save_to_file(&self, path: &str) -> io::Result<()> {
+        let mut path_str = String::from(path);
+        if !path_str.ends_with(".txt") {
+            path_str.push_str(".txt");
+        }
+        let path = Path::new(&path_str);
+        if path.exists() {
+            let err = Error::new(ErrorKind::AlreadyExists, format!("Can't save to '{}'. Path already exists.", path_str));
+            return Err(err);
+        }
+        let mut fout = File::create(path)?;
+        write!(fout, "{}", self)
+    }
+
+    pub fn load_from_file(path: &str) -> io::Result<MazeText> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        MazeText::parse(&text)
+    }
+
+    // Draw the rows of a maze as they are streamed, e.g. by EllersLines, so the maze
+    // is never held in memory. Only the drawing is written, without a header. The maze
+    // is entered at the top left and left at the bottom right.
+    pub fn write_lines<W: Write, I: Iterator<Item = Line>>(out: &mut W, lines: I) -> io::Result<()> {
+        let mut lines = lines.peekable();
+        let mut entry = true;
+        while let Some(line) = lines.next() {
+            if entry {
+                write!(out, "+  +")?;
+                for _ in 1 .. line.side.len() {
+                    write!(out, "--+")?;
+                }
+                writeln!(out)?;
+                entry = false;
+            }
+            write!(out, "|")?;
+            for &closed in &line.side {
+                write!(out, "  {}", if closed { "|" } else { " " })?;
+            }
+            writeln!(out)?;
+            let last = lines.peek().is_none();
+            write!(out, "+")?;
+            for (j, &closed) in line.next.iter().enumerate() {
+                let exit = last && j == line.next.len() - 1;
+                write!(out, "{}+", if closed && !exit { "--" } else { "  " })?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MazeText {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let grid = &self.grid;
+        writeln!(f, "maze {} {}", grid.width, grid.height)?;
+        writeln!(f, "boundary {}", boundary_name(grid.boundary()))?;
+        if let Some(algorithm) = self.algorithm {
+            writeln!(f, "algorithm {}", algorithm)?;
+        }
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed {}", seed)?;
+        }
+        for y in 0 .. grid.height {
+            write_walls(f, grid, y, Direction::North)?;
+            for x in 0 .. grid.width {
+                let wall = if grid.has_wall(x, y, Direction::West) { "|" } else { " " };
+                let inside = match grid.crossing(x, y) {
+                    Some(Crossing::Horizontal) => "==",
+                    Some(Crossing::Vertical)   => "||",
+                    None                       => "  "
+                };
+                write!(f, "{}{}", wall, inside)?;
+            }
+            let wall = if grid.has_wall(grid.width - 1, y, Direction::East) { "|" } else { " " };
+            writeln!(f, "{}", wall)?;
+        }
+        write_walls(f, grid, grid.height - 1, Direction::South)
+    }
+}
+
+fn write_walls(f: &mut fmt::Formatter, grid: &Grid, y: usize, dir: Direction) -> fmt::Result {
+    write!(f, "+")?;
+    for x in 0 .. grid.width {
+        let wall = if grid.has_wall(x, y, dir) { "--" } else { "  " };
+        write!(f, "{}+", wall)?;
+    }
+    writeln!(f)
+}
+
+fn corner(line: usize, c: char) -> io::Result<()> {
+    match c {
+        '+' => Ok(()),
+        _   => Err(invalid(line, "expected a '+' at every corner"))
+    }
+}
+
+// Whether the wall between two cells of a row is open
+fn side_open(line: usize, c: char) -> io::Result<bool> {
+    match c {
+        '|' => Ok(false),
+        ' ' => Ok(true),
+        _   => Err(invalid(line, "expected '|' or ' ' between cells"))
+    }
+}
+
+fn invalid(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid maze text at line {}: {}.", line, msg))
+}
+
+fn boundary_name(boundary: Boundary) -> &'static str {
+    match boundary {
+        Boundary::Bounded  => "bounded",
+        Boundary::Cylinder => "cylinder",
+        Boundary::Torus    => "torus",
+        Boundary::Moebius  => "moebius"
+    }
+}
+
+fn parse_boundary(name: &str) -> Option<Boundary> {
+    [Boundary::Bounded, Boundary::Cylinder, Boundary::Torus, Boundary::Moebius].iter()
+        .cloned()
+        .find(|&boundary| boundary_name(boundary) == name)
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Algorithm::BinaryTree(bias)            => write!(f, "binary-tree bias={}", bias_name(bias)),
+            Algorithm::EllersAlgorithm(scan)       => write!(f, "ellers scan={}", scan_name(scan)),
+            Algorithm::RecursiveBacktracking       => write!(f, "recursive-backtracking"),
+            Algorithm::HuntKillAlgorithm           => write!(f, "hunt-kill"),
+            Algorithm::PrimsAlgorithm              => write!(f, "prims"),
+            Algorithm::GrowingTree(selection)      => {
+                write!(f, "growing-tree selection={}", selection_name(selection))
+            }
+            Algorithm::SidewinderAlgorithm(scan)   => write!(f, "sidewinder scan={}", scan_name(scan)),
+            Algorithm::KruskalsAlgorithm           => write!(f, "kruskals"),
+            Algorithm::RecursiveDivision           => write!(f, "recursive-division"),
+            Algorithm::RecursiveDivisionWith(division) => {
+                write!(f, "recursive-division rooms={} centering={} passages={}", division.room_size,
+                    division.centering, division.passages)
+            }
+            Algorithm::BlobbyDivision(cells)       => write!(f, "blobby-division cells={}", cells),
+            Algorithm::CellularAutomaton(automaton) => {
+                write!(f, "cellular-automaton rule={} steps={} density={}", rule_name(automaton.rule),
+                    automaton.steps, automaton.density)
+            }
+            Algorithm::OriginShift(steps)          => write!(f, "origin-shift steps={}", steps),
+            Algorithm::WeaveKruskalsAlgorithm(d)   => write!(f, "weave-kruskals density={}", d),
+            Algorithm::HuntKillAlgorithmWith(hunt) => write!(f, "hunt-kill hunt={}", hunt_name(hunt)),
+            Algorithm::PrimsAlgorithmWith(prims)   => write!(f, "prims cost={}", cost_name(prims))
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(text: &str) -> io::Result<Algorithm> {
+        parse_algorithm(text)
+            .map_err(|msg| Error::new(ErrorKind::InvalidData, format!("Invalid algorithm '{}': {}.", text, msg)))
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = Error;
+
+    fn from_str(name: &str) -> io::Result<Boundary> {
+        parse_boundary(name)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("Unknown boundary '{}'.", name)))
+    }
+}
+
+// The name of the algorithm with its default settings, then every setting in turn
+fn parse_algorithm(text: &str) -> Result<Algorithm, String> {
+    let mut words = text.split_whitespace();
+    let name = words.next().unwrap_or("");
+    let mut algorithm = match name {
+        "binary-tree"            => Algorithm::BinaryTree(Bias::Northeast),
+        "ellers"                 => Algorithm::EllersAlgorithm(Scan::Horizontal),
+        "recursive-backtracking" => Algorithm::RecursiveBacktracking,
+        "hunt-kill"              => Algorithm::HuntKillAlgorithm,
+        "prims"                  => Algorithm::PrimsAlgorithm,
+        "growing-tree"           => Algorithm::GrowingTree(CellSelection::Newest),
+        "sidewinder"             => Algorithm::SidewinderAlgorithm(Scan::Horizontal),
+        "kruskals"               => Algorithm::KruskalsAlgorithm,
+        "recursive-division"     => Algorithm::RecursiveDivision,
+        "blobby-division"        => Algorithm::BlobbyDivision(1),
+        "cellular-automaton"     => Algorithm::CellularAutomaton(Automaton::default()),
+        "origin-shift"           => Algorithm::OriginShift(10),
+        "weave-kruskals"         => Algorithm::WeaveKruskalsAlgorithm(50),
+        _                        => return Err(format!("unknown name '{}'", name))
+    };
+    for word in words {
+        let mut parts = word.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => algorithm = parse_setting(name, with_settings(algorithm), key, value)?,
+            _                        => return Err(format!("expected a key=value setting, got '{}'", word))
+        }
+    }
+    Ok(algorithm)
+}
+
+fn parse_setting(name: &str, algorithm: Algorithm, key: &str, value: &str) -> Result<Algorithm, String> {
+    let invalid = || format!("invalid {} '{}'", key, value);
+    let number = || value.parse::<usize>().map_err(|_| invalid());
+    // Rooms, passages and cells can't be 0
+    let size = || number().and_then(|size| if size > 0 { Ok(size) } else { Err(invalid()) });
+    let algorithm = match (algorithm, key) {
+        (Algorithm::BinaryTree(_), "bias") => Algorithm::BinaryTree(parse_bias(value).ok_or_else(invalid)?),
+        (Algorithm::EllersAlgorithm(_), "scan") => Algorithm::EllersAlgorithm(parse_scan(value).ok_or_else(invalid)?),
+        (Algorithm::HuntKillAlgorithmWith(_), "hunt") => {
+            Algorithm::HuntKillAlgorithmWith(parse_name(value, &HUNTS, hunt_name).ok_or_else(invalid)?)
+        }
+        (Algorithm::PrimsAlgorithmWith(_), "cost") => {
+            Algorithm::PrimsAlgorithmWith(parse_name(value, &COSTS, cost_name).ok_or_else(invalid)?)
+        }
+        (Algorithm::GrowingTree(_), "selection") => {
+            Algorithm::GrowingTree(parse_selection(value).ok_or_else(invalid)?)
+        }
+        (Algorithm::SidewinderAlgorithm(_), "scan") => {
+            Algorithm::SidewinderAlgorithm(parse_scan(value).ok_or_else(invalid)?)
+        }
+        (Algorithm::RecursiveDivisionWith(division), "rooms") => {
+            Algorithm::RecursiveDivisionWith(Division { room_size: size()?, ..division })
+        }
+        (Algorithm::RecursiveDivisionWith(division), "centering") => {
+            Algorithm::RecursiveDivisionWith(Division { centering: number()?, ..division })
+        }
+        (Algorithm::RecursiveDivisionWith(division), "passages") => {
+            Algorithm::RecursiveDivisionWith(Division { passages: size()?, ..division })
+        }
+        (Algorithm::BlobbyDivision(_), "cells") => Algorithm::BlobbyDivision(size()?),
+        (Algorithm::CellularAutomaton(automaton), "rule") => {
+            let rule = parse_name(value, &RULES, rule_name).ok_or_else(invalid)?;
+            Algorithm::CellularAutomaton(Automaton { rule, ..automaton })
+        }
+        (Algorithm::CellularAutomaton(automaton), "steps") => {
+            Algorithm::CellularAutomaton(Automaton { steps: number()?, ..automaton })
+        }
+        (Algorithm::CellularAutomaton(automaton), "density") => {
+            Algorithm::CellularAutomaton(Automaton { density: number()?, ..automaton })
+        }
+        (Algorithm::OriginShift(_), "steps") => Algorithm::OriginShift(number()?),
+        (Algorithm::WeaveKruskalsAlgorithm(_), "density") => Algorithm::WeaveKruskalsAlgorithm(number()?),
+        _ => return Err(format!("{} takes no {}", name, key))
+    };
+    Ok(algorithm)
+}
+
+// The variant of an algorithm that carries its settings, with the defaults of the one
+// that doesn't
+fn with_settings(algorithm: Algorithm) -> Algorithm {
+    match algorithm {
+        Algorithm::RecursiveDivision => Algorithm::RecursiveDivisionWith(Division::default()),
+        Algorithm::HuntKillAlgorithm => Algorithm::HuntKillAlgorithmWith(Hunt::Columns),
+        Algorithm::PrimsAlgorithm    => Algorithm::PrimsAlgorithmWith(Prims::Simplified),
+        _                            => algorithm
+    }
+}
+
+const BIASES: [Bias; 4] = [Bias::Northeast, Bias::Northwest, Bias::Southeast, Bias::Southwest];
+const SCANS: [Scan; 2] = [Scan::Horizontal, Scan::Vertical];
+const HUNTS: [Hunt; 4] = [Hunt::Columns, Hunt::Rows, Hunt::Random, Hunt::Serpentine];
+const COSTS: [Prims; 3] = [Prims::Simplified, Prims::Cells, Prims::Edges];
+const RULES: [Rule; 2] = [Rule::Maze, Rule::Mazectric];
+
+// The value whose name it is
+fn parse_name<T: Copy>(text: &str, values: &[T], name: fn(T) -> &'static str) -> Option<T> {
+    values.iter().cloned().find(|&value| name(value) == text)
+}
+
+// The corners are also written "ne", "nw", "se" and "sw"
+fn parse_bias(text: &str) -> Option<Bias> {
+    match text {
+        "ne" => Some(Bias::Northeast),
+        "nw" => Some(Bias::Northwest),
+        "se" => Some(Bias::Southeast),
+        "sw" => Some(Bias::Southwest),
+        _    => parse_name(text, &BIASES, bias_name)
+    }
+}
+
+fn bias_name(bias: Bias) -> &'static str {
+    match bias {
+        Bias::Northeast => "northeast",
+        Bias::Northwest => "northwest",
+        Bias::Southeast => "southeast",
+        Bias::Southwest => "southwest"
+    }
+}
+
+// The scans are also written "h" and "v"
+fn parse_scan(text: &str) -> Option<Scan> {
+    match text {
+        "h" => Some(Scan::Horizontal),
+        "v" => Some(Scan::Vertical),
+        _   => parse_name(text, &SCANS, scan_name)
+    }
+}
+
+fn scan_name(scan: Scan) -> &'static str {
+    match scan {
+        Scan::Horizontal => "horizontal",
+        Scan::Vertical   => "vertical"
+    }
+}
+
+fn hunt_name(hunt: Hunt) -> &'static str {
+    match hunt {
+        Hunt::Columns    => "columns",
+        Hunt::Rows       => "rows",
+        Hunt::Random     => "random",
+        Hunt::Serpentine => "serpentine"
+    }
+}
+
+fn cost_name(prims: Prims) -> &'static str {
+    match prims {
+        Prims::Simplified => "none",
+        Prims::Cells      => "cells",
+        Prims::Edges      => "edges"
+    }
+}
+
+fn rule_name(rule: Rule) -> &'static str {
+    match rule {
+        Rule::Maze      => "maze",
+        Rule::Mazectric => "mazectric"
+    }
+}
+
+fn selection_name(selection: CellSelection) -> String {
+    match selection {
+        CellSelection::Newest                       => String::from("newest"),
+        CellSelection::Oldest                       => String::from("oldest"),
+        CellSelection::Random                       => String::from("random"),
+        CellSelection::Middle                       => String::from("middle"),
+        CellSelection::NewestOldest(p)              => format!("newest-oldest:{}", p),
+        CellSelection::NewestRandom(p)              => format!("newest-random:{}", p),
+        CellSelection::OldestRandom(p)              => format!("oldest-random:{}", p),
+        CellSelection::FarthestRandom(p)            => format!("farthest-random:{}", p),
+        CellSelection::Mix { newest, oldest, random } => format!("mix:{},{},{}", newest, oldest, random)
+    }
+}
+
+fn parse_selection(text: &str) -> Option<CellSelection> {
+    let mut parts = text.splitn(2, ':');
+    let selection = match (parts.next()?, parts.next()) {
+        ("newest", None)             => CellSelection::Newest,
+        ("oldest", None)             => CellSelection::Oldest,
+        ("random", None)             => CellSelection::Random,
+        ("middle", None)             => CellSelection::Middle,
+        ("newest-oldest", Some(p))   => CellSelection::NewestOldest(parse_percent(p)?),
+        ("newest-random", Some(p))   => CellSelection::NewestRandom(parse_percent(p)?),
+        ("oldest-random", Some(p))   => CellSelection::OldestRandom(parse_percent(p)?),
+        ("farthest-random", Some(p)) => CellSelection::FarthestRandom(parse_percent(p)?),
+        ("mix", Some(weights))       => {
+            let weights = weights.split(',').map(|weight| weight.parse().ok()).collect::<Option<Vec<_>>>()?;
+            match weights[..] {
+                [0, 0, 0]                => return None,
+                [newest, oldest, random] => CellSelection::Mix { newest, oldest, random },
+                _                        => return None
+            }
+        }
+        _                            => return None
+    };
+    Some(selection)
+}
+
+fn parse_percent(text: &str) -> Option<usize> {
+    text.parse().ok().filter(|&percent| percent <= 100)
+}